@@ -48,6 +48,8 @@ const Dictionaries = {
     USB: 'usb',
     NFC: 'nfc',
     BLE: 'ble',
+    SMART_CARD: 'smart-card',
+    HYBRID: 'hybrid',
     INTERNAL: 'internal',
   },
 }
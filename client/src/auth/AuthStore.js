@@ -20,6 +20,9 @@ export default class AuthStore {
                 let json_response = JSON.parse(response)
                 if (json_response.status == 'ok') {
                     console.log('login succeeded', json_response)
+                    if (json_response.warning) {
+                        console.warn(json_response.warning)
+                    }
                     this.setUser(name)
                 }
             }).catch(error => {
@@ -28,6 +31,7 @@ export default class AuthStore {
     }
 
     signout() {
+        sessionStorage.removeItem('apiSession')
         this.setUser(null)
         // In case we want to interact with the auth server in the future
         return Promise.resolve(null)
@@ -1,7 +1,9 @@
 import React, { useState, useEffect } from 'react';
 
-import { Center, Container, Skeleton } from '@chakra-ui/react';
+import { Center, Container, Skeleton, Button, useToast } from '@chakra-ui/react';
 import { useAuth } from '../../auth'
+import { addCredential } from '../../webauthn'
+import { setSuccess, setWarning, setError } from '../toast'
 
 import UserProfile from './UserProfile'
 import UserCredential from './UserCredential'
@@ -57,6 +59,7 @@ export default function User(props) {
     const auth = useAuth()
     const [user, setUser] = useState(null)
     const [credentials, setCredentials] = useState(null)
+    const toast = useToast()
 
     useEffect(() => {
         if (!auth.isLoggedIn) {
@@ -79,6 +82,20 @@ export default function User(props) {
 
     }, [])
 
+    const handleAddCredential = () => {
+        addCredential(user)
+            .then(response => {
+                setSuccess(toast, 'Credential added.')
+                const warning = JSON.parse(response).warning
+                if (warning) {
+                    setWarning(toast, warning)
+                }
+                return getUserCredentials(user)
+            })
+            .then(credentials => setCredentials(credentials))
+            .catch(error => setError(toast, error.message))
+    }
+
     if (user === null) {
         return (
             <EmptyUser />
@@ -90,6 +107,7 @@ export default function User(props) {
             <Container m="2rem" maxWidth="800">
                 <UserProfile user={user} />
                 <UserCredential credential={credentials} />
+                <Button mt="1rem" colorScheme="teal" onClick={handleAddCredential}>Add a credential</Button>
             </Container >
         </Center >
     )
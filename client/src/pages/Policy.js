@@ -2,7 +2,7 @@ import React from 'react'
 import { observer } from 'mobx-react-lite'
 import {
     Container, Heading, Spacer, Box,
-    FormControl, FormLabel, Input, Button, Radio, RadioGroup,
+    FormControl, FormLabel, FormErrorMessage, Input, Button, Radio, RadioGroup,
     CheckboxGroup, Checkbox, Stack, useCheckboxGroup,
     IconButton, Text, Flex, InputGroup, InputLeftElement, Tooltip
 } from '@chakra-ui/react'
@@ -19,6 +19,10 @@ const Policy = observer(() => {
     const { isLoading } = policy
 
 
+    const handlePreview = () => {
+        policy.preview()
+    }
+
     const handleUpdate = () => {
         console.log("Updating policy")
         policy.patch()
@@ -50,6 +54,7 @@ const Policy = observer(() => {
                     tooltip="The rpID is always the effective domain"
                     value={policy.rpId}
                     isDirty={policy.dirty('rpId')}
+                    error={policy.violation('rpId')}
                     reset={handleFieldReset}
                     onChange={event => policy.setRpId(event.target.value)}
                 />
@@ -60,24 +65,66 @@ const Policy = observer(() => {
                     value={policy.rpName}
                     onChange={event => policy.setRpName(event.target.value)}
                     isDirty={policy.dirty('rpName')}
+                    error={policy.violation('rpName')}
                     reset={handleFieldReset}
                 />
                 <ManagedInput
-                    label="Origin"
+                    label="Origins"
                     id="origin"
-                    tooltip="The Relying Party url"
+                    tooltip="Comma separated origins, e.g. https://example.com, https://*.example.com, android:apk-key-hash:..."
                     value={policy.origin}
                     onChange={event => policy.setOrigin(event.target.value)}
                     isDirty={policy.dirty('origin')}
+                    error={policy.violation('origin')}
+                    reset={handleFieldReset}
+                />
+                <ManagedInput
+                    label="Related Origins"
+                    id="relatedOrigins"
+                    tooltip="Comma separated origins on other domains, served at /.well-known/webauthn"
+                    value={policy.relatedOrigins}
+                    onChange={event => policy.setRelatedOrigins(event.target.value)}
+                    isDirty={policy.dirty('relatedOrigins')}
+                    error={policy.violation('relatedOrigins')}
+                    reset={handleFieldReset}
+                />
+                <ManagedCheckbox
+                    isChecked={policy.crossOrigin}
+                    onChange={event => policy.setCrossOrigin(event.target.checked)}
+                    label="Allow Cross Origin"
+                    id="crossOrigin"
+                    tooltip="Accept ceremonies from an iframe embedded on another site"
+                    isDirty={policy.dirty('crossOrigin')}
+                    reset={handleFieldReset}
+                />
+                <ManagedInput
+                    label="Top Origins"
+                    id="topOrigins"
+                    tooltip="Comma separated sites that may embed a cross origin ceremony. Empty accepts any."
+                    value={policy.topOrigins}
+                    onChange={event => policy.setTopOrigins(event.target.value)}
+                    isDirty={policy.dirty('topOrigins')}
+                    error={policy.violation('topOrigins')}
                     reset={handleFieldReset}
                 />
                 <ManagedInput
                     label="Time Out"
                     id="timeout"
-                    tooltip="A ceremony will time out at this point"
+                    tooltip="Registration will time out at this point"
                     value={policy.timeout}
                     onChange={event => policy.setTimeout(event.target.value)}
                     isDirty={policy.dirty('timeout')}
+                    error={policy.violation('timeout')}
+                    reset={handleFieldReset}
+                />
+                <ManagedInput
+                    label="Sign In Time Out"
+                    id="authenticationTimeout"
+                    tooltip="Sign in will time out at this point"
+                    value={policy.authenticationTimeout}
+                    onChange={event => policy.setAuthenticationTimeout(event.target.value)}
+                    isDirty={policy.dirty('authenticationTimeout')}
+                    error={policy.violation('authenticationTimeout')}
                     reset={handleFieldReset}
                 />
                 <ManagedCheckbox
@@ -100,6 +147,16 @@ const Policy = observer(() => {
                     isDirty={policy.dirty('userVerification')}
                     reset={handleFieldReset}
                 />
+                <ManagedRadios
+                    label="Sign In User Verification"
+                    id="authenticationUserVerification"
+                    tooltip="Whether the authenticator should verify the user at sign in. Discourage to allow second factor use."
+                    value={policy.authenticationUserVerification}
+                    values={Dictionaries.UserVerificationRequirement}
+                    onChange={policy.setAuthenticationUserVerification}
+                    isDirty={policy.dirty('authenticationUserVerification')}
+                    reset={handleFieldReset}
+                />
 
                 <ManagedRadios
                     label="Authenticator Attachment"
@@ -130,8 +187,10 @@ const Policy = observer(() => {
                 />
                 <Stack direction="row">
                     <Button size="sm" mr="1.2rem" onClick={policy.updateFromModel}>Reset</Button>
+                    <Button size="sm" mr="1.2rem" onClick={handlePreview}>Preview</Button>
                     <Button size="sm" onClick={handleUpdate} colorScheme="blue">Update</Button>
                 </Stack>
+                {policy.dryRun && <PolicyPreview dryRun={policy.dryRun} />}
             </Stack>
             <Box w={"20px"} />
             <Stack minWidth={"500px"} direction="column" shadow="base" borderWidth="2px" borderBottomRadius={'xl'} bg={'gray.50'} p="10" spacing={2}>
@@ -143,6 +202,27 @@ const Policy = observer(() => {
 export default Policy
 
 
+function PolicyPreview(props) {
+    const { dryRun } = props
+    return (
+        <Box mt="1.2rem">
+            <Text fontWeight="bold">{dryRun.valid ? 'The update would change' : 'The update would be refused'}</Text>
+            {dryRun.diff.length === 0 && <Text>Nothing</Text>}
+            {dryRun.diff.map(change =>
+                <Text key={change.path} fontSize="sm">
+                    {change.path}: {JSON.stringify(change.from)} &rarr; {JSON.stringify(change.to)}
+                </Text>
+            )}
+            {dryRun.violations.map((violation, index) =>
+                <Text key={index} fontSize="sm" color="red.500">
+                    {violation.field}: {violation.message}
+                </Text>
+            )}
+        </Box>
+    )
+}
+
+
 function ManagedCheckbox(props) {
     const { id, label, tooltip, isDirty, reset, ...rest } = props
     return (
@@ -181,9 +261,9 @@ function ManagedCheckboxes(props) {
 }
 
 function ManagedInput(props) {
-    const { id, tooltip, label, isDirty, reset, ...rest } = props
+    const { id, tooltip, label, isDirty, reset, error, ...rest } = props
     return (
-        <FormControl mt="1.2rem">
+        <FormControl mt="1.2rem" isInvalid={!!error}>
             <Tooltip label={tooltip}>
                 <FormLabel>{label}</FormLabel>
             </Tooltip>
@@ -191,6 +271,7 @@ function ManagedInput(props) {
                 {isDirty && <InputLeftElement children={<FaRedoAlt id={id} onClick={reset} />} />}
                 <Input id={id} {...rest} />
             </InputGroup>
+            <FormErrorMessage>{error}</FormErrorMessage>
         </FormControl>
     )
 }
@@ -23,6 +23,10 @@ export default function Login(props) {
 
         createCredential({ name, displayName }).then(response => {
             setSuccess(toast, 'Registration successful. Try logging in.')
+            const warning = JSON.parse(response).warning
+            if (warning) {
+                setWarning(toast, warning)
+            }
 
         }).catch(error => setError(error.message))
     }
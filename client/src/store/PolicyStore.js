@@ -1,4 +1,45 @@
 import { makeAutoObservable, runInAction } from "mobx"
+import { apiHeaders } from "../webauthn"
+
+// The section and name of each field in the server's policy
+const FIELDS = {
+    rpId: ['rp', 'id'],
+    rpName: ['rp', 'name'],
+    origin: ['rp', 'origins'],
+    relatedOrigins: ['rp', 'relatedOrigins'],
+    crossOrigin: ['rp', 'crossOrigin'],
+    topOrigins: ['rp', 'topOrigins'],
+    keyType: ['registration', 'keyType'],
+    algs: ['registration', 'algs'],
+    authenticatorAttachment: ['registration', 'authenticatorAttachment'],
+    authenticatorTransports: ['registration', 'authenticatorTransports'],
+    residentKey: ['registration', 'residentKey'],
+    userVerification: ['registration', 'userVerification'],
+    attestation: ['registration', 'attestation'],
+    timeout: ['registration', 'timeout'],
+    authenticationUserVerification: ['authentication', 'userVerification'],
+    authenticationTimeout: ['authentication', 'timeout'],
+    validateSignCount: ['authentication', 'validateSignCount'],
+}
+
+const ORIGIN_FIELDS = ['origin', 'relatedOrigins', 'topOrigins']
+
+const flatten = (policy) => {
+    let model = {}
+    for (const [key, [section, name]] of Object.entries(FIELDS)) {
+        model[key] = policy[section]?.[name]
+    }
+    // Lists of origins are edited as one comma separated field
+    for (const key of ORIGIN_FIELDS) {
+        model[key] = (model[key] || []).join(', ')
+    }
+    return model
+}
+
+const splitOrigins = (value) => value.split(',').map(origin => origin.trim()).filter(origin => origin)
+
+// Fields whose input shows its violation
+const INPUT_FIELDS = ['rpId', 'rpName', 'origin', 'relatedOrigins', 'topOrigins', 'timeout', 'authenticationTimeout']
 
 export default class PolicyStore {
 
@@ -8,14 +49,19 @@ export default class PolicyStore {
         rpId: '',
         rpName: '',
         keyType: '',
-        alg: 0,
+        algs: [],
         authenticatorAttachment: '',
         authenticatorTransports: [],
         residentKey: '',
         userVerification: '',
         origin: '',
+        relatedOrigins: '',
+        crossOrigin: false,
+        topOrigins: '',
         attestation: '',
         timeout: 0,
+        authenticationUserVerification: '',
+        authenticationTimeout: 0,
         validateSignCount: false,
         defaultUserDisplayName: '',
         defaultUserName: '',
@@ -24,18 +70,31 @@ export default class PolicyStore {
     rpId = ''
     rpName = ''
     keyType = ''
-    alg = 0
+    algs = []
     authenticatorAttachment = ''
     authenticatorTransports = []
     residentKey = ''
     userVerification = ''
     origin = ''
+    relatedOrigins = ''
+    crossOrigin = false
+    topOrigins = ''
     attestation = ''
     timeout = 0
+    authenticationUserVerification = ''
+    authenticationTimeout = 0
     validateSignCount = false
     defaultUserDisplayName = ''
     defaultUserName = ''
 
+    // The version of the policy being edited, sent back as If-Match
+    etag = null
+
+    // Why the server refused the last patch, by field
+    violations = {}
+
+    // What the last preview said the patch would change
+    dryRun = null
 
     constructor() {
         makeAutoObservable(this, {}, { autoBind: true })
@@ -51,40 +110,114 @@ export default class PolicyStore {
                 'Content-Type': 'application/json'
             }
         }).then(response => {
+            runInAction(() => { this.etag = response.headers.get('ETag') })
             return response.json()
-        }).then(model => runInAction(() => {
-            this.model = model
+        }).then(policy => runInAction(() => {
+            this.model = flatten(policy)
+            this.dryRun = null
             this.updateFromModel()
             this.isLoading = false
             console.log(JSON.stringify(this.model))
         }))
     }
 
-    patch() {
+    patchSet() {
         let patchSet = {}
         for (const [key, value] of Object.entries(this.model)) {
-            if (this[key] !== value) {
-                patchSet[key] = this[key]
+            if (FIELDS[key] && this[key] !== value) {
+                const [section, name] = FIELDS[key]
+                const patchValue = ORIGIN_FIELDS.includes(key) ? splitOrigins(this[key]) : this[key]
+                patchSet[section] = { ...patchSet[section], [name]: patchValue }
             }
         }
+        return patchSet
+    }
+
+    patchHeaders() {
+        let headers = apiHeaders()
+        if (this.etag) {
+            headers['If-Match'] = this.etag
+        }
+        return headers
+    }
+
+    // Ask the server what the patch would change, without saving it
+    preview() {
+        fetch('/api/admin/policy?dry_run=true', {
+            method: 'PATCH',
+            credentials: 'include',
+            headers: this.patchHeaders(),
+            body: JSON.stringify(this.patchSet())
+        }).then(response => {
+            if (response.status === 412) {
+                console.log('Policy has changed, reloading')
+                this.loadModel()
+                return Promise.reject(response)
+            }
+            if (!response.ok) {
+                return Promise.reject(response)
+            }
+            return response.json()
+        }).then(dryRun => runInAction(() => {
+            this.dryRun = dryRun
+            this.setViolations(dryRun.violations)
+        })).catch(response => {
+            console.log('Policy not previewed', response.status)
+        })
+    }
 
+    patch() {
         this.isLoading = true;
-        fetch('/policy', {
+        fetch('/api/admin/policy', {
             method: 'PATCH',
             credentials: 'include',
-            headers: {
-                'Content-Type': 'application/json'
-            },
-            body: JSON.stringify(patchSet)
+            headers: this.patchHeaders(),
+            body: JSON.stringify(this.patchSet())
         }).then(response => {
+            if (response.status === 412) {
+                // Changed by someone else, start over from their version
+                console.log('Policy has changed, reloading')
+                this.loadModel()
+                return Promise.reject(response)
+            }
+            if (response.status === 400) {
+                return response.json().then(error => {
+                    this.setViolations(error.violations ?? [])
+                    runInAction(() => { this.isLoading = false })
+                    return Promise.reject(response)
+                })
+            }
+            runInAction(() => { this.etag = response.headers.get('ETag') })
             return response.json()
-        }).then(model => runInAction(() => {
-            this.model = model
+        }).then(policy => runInAction(() => {
+            this.model = flatten(policy)
+            this.violations = {}
+            this.dryRun = null
             this.updateFromModel()
             this.isLoading = false
             console.log(JSON.stringify(this.model))
-        }))
+        })).catch(response => {
+            console.log('Policy not saved', response.status)
+        })
+
+    }
+
+    setViolations(violations) {
+        this.violations = {}
+        for (const { field, message } of violations) {
+            const [section, name] = field.split('.')
+            const key = Object.keys(FIELDS)
+                .find(key => FIELDS[key][0] === section && FIELDS[key][1] === name)
+            if (INPUT_FIELDS.includes(key)) {
+                this.violations[key] = message
+            } else {
+                console.log('Policy violation:', field, message)
+            }
+        }
+    }
 
+    violation(key) {
+        return this.violations[key]
     }
 
     updateFromModel() {
@@ -109,14 +242,19 @@ export default class PolicyStore {
             this.dirty('rpid') ||
             this.dirty('rpName') ||
             this.dirty('keyType') ||
-            this.dirty('alg') ||
+            this.dirty('algs') ||
             this.dirty('authenticatorAttachment') ||
             this.dirty('authenticatorTransports') ||
             this.dirty('residentKey') ||
             this.dirty('userVerification') ||
             this.dirty('origin') ||
+            this.dirty('relatedOrigins') ||
+            this.dirty('crossOrigin') ||
+            this.dirty('topOrigins') ||
             this.dirty('attestation') ||
             this.dirty('timeout') ||
+            this.dirty('authenticationUserVerification') ||
+            this.dirty('authenticationTimeout') ||
             this.dirty('validateSignCount') ||
             this.dirty('defaultUserDisplayName') ||
             this.dirty('defaultUserName')
@@ -135,8 +273,8 @@ export default class PolicyStore {
         this.keyType = value
     }
 
-    setAlg(value) {
-        this.alg = value
+    setAlgs(values) {
+        this.algs = values
     }
 
     setAuthenticatorAttachment(value) {
@@ -161,6 +299,18 @@ export default class PolicyStore {
         this.origin = value
     }
 
+    setRelatedOrigins(value) {
+        this.relatedOrigins = value
+    }
+
+    setCrossOrigin(value) {
+        this.crossOrigin = value
+    }
+
+    setTopOrigins(value) {
+        this.topOrigins = value
+    }
+
     setAttestation(value) {
         this.attestation = value
     }
@@ -173,6 +323,15 @@ export default class PolicyStore {
     setTimeout(value) {
         this.timeout = value
     }
+
+    setAuthenticationUserVerification(value) {
+        this.authenticationUserVerification = value
+    }
+
+    setAuthenticationTimeout(value) {
+        this.authenticationTimeout = value
+    }
+
     setDefaultUserName(value) {
         this.defaultUserName = value
     }
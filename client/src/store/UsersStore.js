@@ -1,4 +1,5 @@
 import { makeAutoObservable, runInAction } from "mobx"
+import { apiHeaders } from "../webauthn"
 
 export default class UsersStore {
     isLoading = false;
@@ -10,12 +11,10 @@ export default class UsersStore {
 
     loadUsers() {
         this.isLoading = true;
-        fetch('/api/users', {
+        fetch('/api/admin/users', {
             method: 'GET',
             credentials: 'include',
-            headers: {
-                'Content-Type': 'application/json'
-            }
+            headers: apiHeaders()
         }).catch(err => {
             this.isLoading = false
             console.log('loadUsers failed:', err)
@@ -58,13 +58,11 @@ function publicKeyCredentialToJSON(pubKeyCred) {
     return pubKeyCred
 }
 
-async function sendWebAuthnChallenge(ceremony, formBody) {
+async function sendWebAuthnChallenge(ceremony, formBody, headers = { 'Content-Type': 'application/json' }) {
     const response = await fetch(ENDPOINTS[ceremony].challenge, {
         method: 'POST',
         credentials: 'include',
-        headers: {
-            'Content-Type': 'application/json'
-        },
+        headers: headers,
         body: JSON.stringify(formBody)
     })
 
@@ -107,17 +105,50 @@ async function sendWebAuthnResponse(ceremony, body) {
         throw new Error('Server responded with error.')
     }
 
+    // A successful assertion turns the session into the api login session
+    let apiSession = response.headers.get("X-SESSION")
+    if (apiSession != null) {
+        sessionStorage.setItem("apiSession", apiSession)
+    }
+
     return await response.json()
 }
 
+/**
+ * Headers for calls to the api, including the login session if there is one.
+ */
+export function apiHeaders() {
+    let headers = { 'Content-Type': 'application/json' }
+    let session = sessionStorage.getItem("apiSession")
+    if (session != null) {
+        headers['X-SESSION'] = session
+    }
+    return headers
+}
+
+
+let prfResults = null
+
+/**
+ * The PRF outputs of the last assertion, as ArrayBuffers `first` and, while a
+ * salt rotation is pending, `second`.  Null if PRF was not evaluated.
+ */
+export function getPrfResults() {
+    return prfResults
+}
+
+let largeBlob = null
 
-export async function createCredential(data = {}) {
+export async function createCredential(data = {}, headers) {
     // Request challenge options from the RP
-    const publicKey = await sendWebAuthnChallenge('credential', data)
+    const publicKey = await sendWebAuthnChallenge('credential', data, headers)
 
     // Base64 decode stuff,
     publicKey.challenge = base64url_decode(publicKey.challenge)
     publicKey.user.id = base64url_decode(publicKey.user.id)
+    for (let excludeCred of publicKey.excludeCredentials || []) {
+        excludeCred.id = base64url_decode(excludeCred.id)
+    }
 
     console.log('CREDENTIAL CHALLENGE', publicKey)
 
@@ -125,26 +156,112 @@ export async function createCredential(data = {}) {
     console.log('CREDENTIAL RESPONSE', credential)
 
     const credentialResponse = publicKeyCredentialToJSON(credential)
+    // Methods do not survive the JSON conversion, so send their results
+    if (typeof credential.response.getTransports === 'function') {
+        credentialResponse.response.transports = credential.response.getTransports()
+    }
+    credentialResponse.clientExtensionResults =
+        publicKeyCredentialToJSON(credential.getClientExtensionResults())
     return await sendWebAuthnResponse('credential', credentialResponse)
 }
 
-export async function assertCredential(data = {}) {
-    // Fetch the assertion options from the Verifier, and format it for
-    // the CTAP Authenticator
-    const publicKey = await sendWebAuthnChallenge('assertion', data)
+/**
+ * Register another credential for the signed in `user`.  The login session
+ * shows the RP that the name is ours.
+ */
+export async function addCredential(user) {
+    return await createCredential({ name: user.name, displayName: user.displayName }, apiHeaders())
+}
+
+/**
+ * Decode the request options, get an assertion, and format it for the
+ * Verifier.  Extension results that must stay on the client are kept aside.
+ */
+async function getAssertion(publicKey) {
     publicKey.challenge = base64url_decode(publicKey.challenge)
 
     for (let allowCred of publicKey.allowCredentials) {
         allowCred.id = base64url_decode(allowCred.id)
     }
+    const evalByCredential = publicKey.extensions?.prf?.evalByCredential || {}
+    for (let salts of Object.values(evalByCredential)) {
+        salts.first = base64url_decode(salts.first)
+        if (salts.second) {
+            salts.second = base64url_decode(salts.second)
+        }
+    }
+    if (publicKey.extensions?.largeBlob?.write) {
+        publicKey.extensions.largeBlob.write = base64url_decode(publicKey.extensions.largeBlob.write)
+    }
     console.log('ASSERTION CHALLENGE', publicKey)
 
     // Call the CTAP Authenticator with the options
     const assertion = await navigator.credentials.get({ publicKey })
     const assertionResponse = publicKeyCredentialToJSON(assertion)
+    const extensionResults = assertion.getClientExtensionResults()
+    // The PRF results are key material.  Keep them here, never send them.
+    prfResults = extensionResults.prf?.results || null
+    // A blob that was read is only of use here
+    largeBlob = extensionResults.largeBlob?.blob || null
+    const largeBlobResults = extensionResults.largeBlob
+        ? { ...extensionResults.largeBlob, blob: undefined }
+        : undefined
+    assertionResponse.clientExtensionResults = publicKeyCredentialToJSON({
+        ...extensionResults,
+        prf: undefined,
+        largeBlob: largeBlobResults
+    })
 
     console.log('ASSERTION RESPONSE', JSON.stringify(assertionResponse))
+    return assertionResponse
+}
+
+export async function assertCredential(data = {}) {
+    // Fetch the assertion options from the Verifier, and format it for
+    // the CTAP Authenticator
+    const publicKey = await sendWebAuthnChallenge('assertion', data)
+    const assertionResponse = await getAssertion(publicKey)
+    return await sendWebAuthnResponse('assertion', assertionResponse)
+}
+
+/**
+ * Run an assertion with the credential `id` that reads or writes its large
+ * blob.  `input` is the largeBlob extension input.
+ */
+async function largeBlobAssertion(id, input) {
+    const response = await fetch(`/api/credentials/${id}/large-blob`, {
+        method: 'POST',
+        credentials: 'include',
+        headers: apiHeaders(),
+        body: JSON.stringify(input)
+    })
+
+    if (response.status !== 200) {
+        throw new Error('Server responded with error.')
+    }
+
+    let session = response.headers.get("X-SESSION")
+    if (session != null) {
+        sessionStorage.setItem("session", session)
+    }
+
+    const assertionResponse = await getAssertion(await response.json())
     return await sendWebAuthnResponse('assertion', assertionResponse)
 }
 
+/**
+ * Read the large blob of the credential `id`.  Resolves to an ArrayBuffer, or
+ * null if the authenticator has none.
+ */
+export async function readLargeBlob(id) {
+    await largeBlobAssertion(id, { read: true })
+    return largeBlob
+}
 
+/**
+ * Write `blob`, an ArrayBuffer or typed array, as the large blob of the
+ * credential `id`.  The response has a warning if the write failed.
+ */
+export async function writeLargeBlob(id, blob) {
+    return await largeBlobAssertion(id, { write: base64url_encode(blob) })
+}
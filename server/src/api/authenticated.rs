@@ -0,0 +1,67 @@
+//! Helpers for api requests made on behalf of a signed in user.
+//!
+//! The session named in the X-SESSION header must have completed an
+//! assertion.  Failures are returned as the response to send.
+//!
+use actix_web::{HttpRequest, HttpResponse};
+use base64urlsafedata::Base64UrlSafeData;
+
+use crate::{webauthn::model::Credential, DataServices, Session};
+
+/// The authenticated session, and the name of its user.
+pub async fn authenticated_session(
+    service: &DataServices,
+    req: &HttpRequest,
+) -> Result<(Session, String), HttpResponse> {
+    let session = Session::from_request(service, req).await.map_err(|_| {
+        log::trace!("Request without a session");
+        HttpResponse::Unauthorized().json(r#"{ "message": "Not authenticated" }"#)
+    })?;
+
+    if !session.is_authenticated() {
+        log::trace!("Request on an unauthenticated session");
+        return Err(HttpResponse::Unauthorized().json(r#"{ "message": "Not authenticated" }"#));
+    }
+
+    let name = session
+        .as_str("name")
+        .map_err(|_| HttpResponse::Unauthorized().json(r#"{ "message": "Not authenticated" }"#))?;
+    Ok((session, name))
+}
+
+/// A credential that belongs to the session user.  Credentials of other users
+/// are reported as not found.
+pub async fn owned_credential(
+    service: &DataServices,
+    req: &HttpRequest,
+    id: &str,
+) -> Result<Credential, HttpResponse> {
+    let (_, name) = authenticated_session(service, req).await?;
+    let not_found = || HttpResponse::NotFound().json(r#"{ "message": "Credential not found" }"#);
+
+    let id = Base64UrlSafeData::try_from(id).map_err(|_| not_found())?;
+    let owned = match service.get_stored_user(&name).await {
+        Ok(Some(user)) => user.credentials.is_some_and(|ids| ids.contains(&id)),
+        Ok(None) => false,
+        Err(err) => {
+            log::trace!("Failed fetching user: {}", err);
+            return Err(
+                HttpResponse::InternalServerError().json(r#"{ "message": "Error getting user" }"#)
+            );
+        }
+    };
+    if !owned {
+        log::trace!("Credential does not belong to user: {}", &name);
+        return Err(not_found());
+    }
+
+    match service.get_credential(&id).await {
+        Ok(Some(cred)) => Ok(cred),
+        Ok(None) => Err(not_found()),
+        Err(err) => {
+            log::trace!("Failed fetching credential: {}", err);
+            Err(HttpResponse::InternalServerError()
+                .json(r#"{ "message": "Error getting credential" }"#))
+        }
+    }
+}
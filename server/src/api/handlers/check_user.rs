@@ -1,9 +1,9 @@
 use actix_web::{web, HttpResponse};
 
-use crate::{errors::Error, webauthn::model::UserEntity, DataServices};
+use crate::{errors::Error, webauthn::model::UserEntity, TenantServices};
 
 pub async fn check_user(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     request: web::Json<UserEntity>,
 ) -> Result<HttpResponse, Error> {
     // See if this user already exists.  If so, return 403
@@ -16,6 +16,7 @@ pub async fn check_user(
         )));
     }
 
-    // Return the PK Options
-    Ok(HttpResponse::Ok().finish())
+    // Tell the client whether it can offer sign in without a user name
+    let usernameless = service.can_sign_in_without_username(&request.name).await?;
+    Ok(HttpResponse::Ok().json(format!(r#"{{"usernameless": {}}}"#, usernameless)))
 }
@@ -0,0 +1,34 @@
+/// Finish a PRF salt rotation.  The new salt replaces the old one, and is
+/// returned as `first`.
+///
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    api::owned_credential, errors::Error, webauthn::model::AuthenticationExtensionsPrfValues,
+    TenantServices,
+};
+
+pub async fn commit_prf_salt(
+    path: web::Path<(String,)>,
+    service: TenantServices,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (id,) = path.into_inner();
+    log::trace!("Commit PRF Salt Request: {}", &id);
+
+    let cred = match owned_credential(&service, &req, &id).await {
+        Ok(cred) => cred,
+        Err(response) => return Ok(response),
+    };
+
+    match service.commit_prf_salt(&cred).await {
+        Ok(cred) => {
+            let values = AuthenticationExtensionsPrfValues::for_credential(&cred);
+            Ok(HttpResponse::Ok().json(values))
+        }
+        Err(Error::PrfError(message)) => {
+            Ok(HttpResponse::Conflict().json(format!(r#"{{"message": "{}"}}"#, message)))
+        }
+        Err(err) => Err(err),
+    }
+}
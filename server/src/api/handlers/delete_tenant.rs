@@ -0,0 +1,18 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{api::require_default_tenant, errors::Error, TenantServices};
+
+/// Delete a tenant with its users, credentials and challenges
+pub async fn delete_tenant(
+    path: web::Path<(String,)>,
+    service: TenantServices,
+) -> Result<HttpResponse, Error> {
+    if let Err(response) = require_default_tenant(&service) {
+        return Ok(response);
+    }
+    let (tenant,) = path.into_inner();
+    log::trace!("Delete Tenant Request: {}", &tenant);
+
+    service.delete_tenant(&tenant).await?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,10 +1,10 @@
 use actix_web::{web, HttpResponse};
 
-use crate::{errors::Error, DataServices};
+use crate::{errors::Error, TenantServices};
 
 pub async fn delete_user(
     path: web::Path<(String,)>,
-    service: web::Data<DataServices>,
+    service: TenantServices,
 ) -> Result<HttpResponse, Error> {
     let (name,) = path.into_inner();
     log::trace!("Delete User Request: {}", &name);
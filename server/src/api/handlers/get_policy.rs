@@ -1,10 +1,12 @@
-use actix_web::{web, HttpResponse};
+use actix_web::HttpResponse;
 
-use crate::{config::AppConfig, errors::Error, DataServices};
+use crate::{api::config_etag, config::AppConfig, errors::Error, TenantServices};
 
-pub async fn get_policy(service: web::Data<DataServices>) -> Result<HttpResponse, Error> {
+pub async fn get_policy(service: TenantServices) -> Result<HttpResponse, Error> {
     let config: AppConfig = service.get_config().await?;
     log::trace!("Sending policy: {:?}", &config);
 
-    Ok(HttpResponse::Ok().json(&config.webauthn))
+    Ok(HttpResponse::Ok()
+        .insert_header(config_etag(&config))
+        .json(&config.webauthn))
 }
@@ -0,0 +1,9 @@
+use actix_web::HttpResponse;
+
+use crate::{errors::Error, TenantServices};
+
+/// The history of the config, newest first
+pub async fn get_policy_versions(service: TenantServices) -> Result<HttpResponse, Error> {
+    let changes = service.get_config_changes().await?;
+    Ok(HttpResponse::Ok().json(changes))
+}
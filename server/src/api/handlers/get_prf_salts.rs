@@ -0,0 +1,28 @@
+/// The PRF salts of one of the session user's credentials, in the form used
+/// for `evalByCredential`.  `second` is present while a rotation is pending.
+///
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    api::owned_credential, errors::Error, webauthn::model::AuthenticationExtensionsPrfValues,
+    TenantServices,
+};
+
+pub async fn get_prf_salts(
+    path: web::Path<(String,)>,
+    service: TenantServices,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (id,) = path.into_inner();
+    log::trace!("Get PRF Salts Request: {}", &id);
+
+    let cred = match owned_credential(&service, &req, &id).await {
+        Ok(cred) => cred,
+        Err(response) => return Ok(response),
+    };
+
+    match AuthenticationExtensionsPrfValues::for_credential(&cred) {
+        Some(values) => Ok(HttpResponse::Ok().json(values)),
+        None => Ok(HttpResponse::NotFound().json(r#"{ "message": "PRF is not enabled" }"#)),
+    }
+}
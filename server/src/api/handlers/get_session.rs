@@ -0,0 +1,35 @@
+/// The state of the session named in the X-SESSION header, so that the client
+/// can tell when to ask for a fresh assertion with user verification before a
+/// sensitive operation.
+///
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{api::authenticated_session, errors::Error, TenantServices};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionState {
+    name: String,
+    authenticated_at: Option<DateTime<Utc>>,
+    user_verified: bool,
+    user_verified_at: Option<DateTime<Utc>>,
+    device_bound: bool,
+}
+
+pub async fn get_session(service: TenantServices, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let (session, name) = match authenticated_session(&service, &req).await {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    let state = SessionState {
+        name,
+        authenticated_at: session.as_time("authenticated_at").ok(),
+        user_verified: session.is_user_verified(),
+        user_verified_at: session.as_time("user_verified_at").ok(),
+        device_bound: session.get("device_bound").as_deref() == Some("true"),
+    };
+    Ok(HttpResponse::Ok().json(state))
+}
@@ -0,0 +1,12 @@
+use actix_web::HttpResponse;
+
+use crate::{api::require_default_tenant, errors::Error, TenantServices};
+
+/// The config of every tenant
+pub async fn get_tenants(service: TenantServices) -> Result<HttpResponse, Error> {
+    if let Err(response) = require_default_tenant(&service) {
+        return Ok(response);
+    }
+    let tenants = service.get_tenants().await?;
+    Ok(HttpResponse::Ok().json(tenants))
+}
@@ -1,10 +1,10 @@
 use actix_web::{web, HttpResponse};
 
-use crate::{errors::Error, webauthn::model::UserEntity, DataServices};
+use crate::{errors::Error, webauthn::model::UserEntity, TenantServices};
 
 /// Get a user via HTTP Post
 pub async fn get_user(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     request: web::Json<UserEntity>,
 ) -> Result<HttpResponse, Error> {
     // See if this user already exists.  If so, return 403
@@ -1,9 +1,9 @@
 use actix_web::{web, HttpResponse};
 
-use crate::{errors::Error, webauthn::model::UserEntity, DataServices};
+use crate::{errors::Error, webauthn::model::UserEntity, TenantServices};
 
 pub async fn get_user_credentials(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     request: web::Json<UserEntity>,
 ) -> Result<HttpResponse, Error> {
     // See if this user already exists.  If so, return 403
@@ -1,8 +1,8 @@
-use actix_web::{web, HttpResponse};
+use actix_web::HttpResponse;
 
-use crate::{errors::Error, DataServices};
+use crate::{errors::Error, TenantServices};
 
-pub async fn get_users(service: web::Data<DataServices>) -> Result<HttpResponse, Error> {
+pub async fn get_users(service: TenantServices) -> Result<HttpResponse, Error> {
     let result = service.get_users().await;
     match result {
         Ok(users) => {
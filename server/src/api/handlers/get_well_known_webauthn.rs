@@ -0,0 +1,21 @@
+/// The WebAuthn Related Origin Requests document.  Clients fetch it from
+/// `https://{rp.id}/.well-known/webauthn` when a page on another domain asks
+/// to use the RP id.
+///
+use actix_web::HttpResponse;
+use serde::Serialize;
+
+use crate::{config::AppConfig, errors::Error, webauthn::model::AllowedOrigin, TenantServices};
+
+#[derive(Debug, Serialize)]
+struct WellKnownWebauthn {
+    origins: Vec<AllowedOrigin>,
+}
+
+pub async fn get_well_known_webauthn(service: TenantServices) -> Result<HttpResponse, Error> {
+    let config: AppConfig = service.get_config().await?;
+    let well_known = WellKnownWebauthn {
+        origins: config.webauthn.rp.related_origins,
+    };
+    Ok(HttpResponse::Ok().json(well_known))
+}
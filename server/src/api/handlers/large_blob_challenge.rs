@@ -0,0 +1,85 @@
+/// Start an assertion that reads or writes the large blob of one of the
+/// session user's credentials.  The body is the `largeBlob` input, with
+/// either `read` or `write` set.  The assertion is sent to the usual
+/// assertion response endpoint, which verifies that a write succeeded.
+///
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    api::{authenticated_session, owned_credential},
+    errors::Error,
+    services::{Ceremony, ChallengeBinding},
+    webauthn::model::{
+        AuthenticationExtensionsLargeBlobInputs, PublicKeyCredentialDiscriptor,
+        PublicKeyCredentialRequestOptionsBuilder,
+    },
+    Session, TenantServices,
+};
+
+pub async fn large_blob_challenge(
+    path: web::Path<(String,)>,
+    service: TenantServices,
+    request: web::Json<AuthenticationExtensionsLargeBlobInputs>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (id,) = path.into_inner();
+    log::trace!("Large Blob Challenge Request: {}", &id);
+
+    let name = match authenticated_session(&service, &req).await {
+        Ok((_, name)) => name,
+        Err(response) => return Ok(response),
+    };
+    let cred = match owned_credential(&service, &req, &id).await {
+        Ok(cred) => cred,
+        Err(response) => return Ok(response),
+    };
+    if !cred.large_blob_supported {
+        return Ok(HttpResponse::Conflict()
+            .json(r#"{ "message": "Large blobs are not supported by this credential" }"#));
+    }
+
+    let config = service.get_config().await?;
+    let user = match service.get_user(&name).await? {
+        Some(user) => user,
+        None => {
+            return Ok(HttpResponse::NotFound().json(r#"{ "message": "User not found" }"#));
+        }
+    };
+
+    // A new session holds the challenge, as in assertion_challenge
+    let session = Session::default();
+    let binding = ChallengeBinding {
+        ceremony: Ceremony::Get,
+        user_handle: user.id.clone(),
+        rp_id: config.webauthn.rp.id.clone(),
+        session_id: session.id.clone(),
+    };
+    let challenge = service
+        .create_new_challenge(binding, config.webauthn.authentication_challenge_ttl())
+        .await?;
+
+    let builder = PublicKeyCredentialRequestOptionsBuilder::from(&config.webauthn)
+        .with_challenge(&challenge.value)
+        .with_allow_credentials(vec![PublicKeyCredentialDiscriptor::try_from(&cred)?]);
+    let builder = match (&request.write, request.read) {
+        (Some(blob), None) => builder.with_large_blob_write(&blob.0),
+        (None, Some(true)) => builder.with_large_blob_read(),
+        _ => {
+            return Ok(HttpResponse::BadRequest()
+                .json(r#"{ "message": "Set exactly one of read or write" }"#));
+        }
+    };
+    let pk_options = builder.build()?;
+
+    let mut session = session
+        .with("name", &name)
+        .with("challenge", &pk_options.challenge.to_string());
+    if request.write.is_some() {
+        session.insert("large_blob_write", &cred.id.to_string());
+    }
+    session.put_session(&service).await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(session.to_header())
+        .json(pk_options))
+}
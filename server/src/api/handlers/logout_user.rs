@@ -1,14 +1,15 @@
-/// Logging does not actually do anything.  If this app used session tokens,
-/// then logging out would clean up or delete the token.
+/// Logging out deletes the session named in the X-SESSION header, if there is
+/// one, so that it can no longer be used for the admin api.
 ///
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 
-use crate::{errors::Error, webauthn::model::UserEntity, DataServices};
+use crate::{errors::Error, webauthn::model::UserEntity, Session, TenantServices};
 
 /// Get a user via HTTP Post
 pub async fn logout_user(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     request: web::Json<UserEntity>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     // See if this user already exists.  If so, return 403
     let user = service.check_user(&request.name).await?;
@@ -20,6 +21,10 @@ pub async fn logout_user(
         )));
     }
 
-    // Return the PK Options
+    if let Ok(session) = Session::from_request(&service, &req).await {
+        log::trace!("Deleting session for user: {}", &request.name);
+        service.delete_session(&session.id).await?;
+    }
+
     Ok(HttpResponse::Ok().finish())
 }
@@ -1,21 +1,45 @@
 pub use check_user::check_user;
+pub use commit_prf_salt::commit_prf_salt;
+pub use delete_tenant::delete_tenant;
 pub use delete_user::delete_user;
 pub use get_policy::get_policy;
+pub use get_policy_versions::get_policy_versions;
+pub use get_prf_salts::get_prf_salts;
+pub use get_session::get_session;
+pub use get_tenants::get_tenants;
 pub use get_user::get_user;
 pub use get_user_credentials::get_user_credentials;
 pub use get_users::get_users;
+pub use get_well_known_webauthn::get_well_known_webauthn;
+pub use large_blob_challenge::large_blob_challenge;
 pub use logout_user::logout_user;
 pub use patch_policy::patch_policy;
+pub use put_tenant::put_tenant;
+pub use put_user_roles::put_user_roles;
 pub use refresh_mds::refresh_mds;
+pub use rollback_policy::rollback_policy;
+pub use rotate_prf_salt::rotate_prf_salt;
 pub use search_mds::search_mds;
 
 pub mod check_user;
+pub mod commit_prf_salt;
+pub mod delete_tenant;
 pub mod delete_user;
 pub mod get_policy;
+pub mod get_policy_versions;
+pub mod get_prf_salts;
+pub mod get_session;
+pub mod get_tenants;
 pub mod get_user;
 pub mod get_user_credentials;
 pub mod get_users;
+pub mod get_well_known_webauthn;
+pub mod large_blob_challenge;
 pub mod logout_user;
 pub mod patch_policy;
+pub mod put_tenant;
+pub mod put_user_roles;
 pub mod refresh_mds;
+pub mod rollback_policy;
+pub mod rotate_prf_salt;
 pub mod search_mds;
@@ -1,11 +1,57 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
 
-use crate::{errors::Error, webauthn::model::WebauthnPolicyBuilder, DataServices};
+use crate::{
+    api::{authenticated_session, config_etag, if_match_version, version_conflict},
+    errors::Error,
+    webauthn::model::WebauthnPolicyBuilder,
+    TenantServices,
+};
 
+#[derive(Debug, Deserialize)]
+pub struct PatchPolicyParams {
+    /// Report what the patch would change, and anything wrong with it,
+    /// without saving it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Patch the policy.  The change is recorded in the config history under the
+/// admin's name.  An invalid policy is refused with the violations.
 pub async fn patch_policy(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     policy: web::Json<WebauthnPolicyBuilder>,
+    params: web::Query<PatchPolicyParams>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
-    let policy = service.patch_policy(policy.into_inner()).await?;
-    Ok(HttpResponse::Ok().json(policy))
+    let (_, name) = match authenticated_session(&service, &req).await {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+    let expected_version = match if_match_version(&req) {
+        Ok(version) => version,
+        Err(response) => return Ok(response),
+    };
+
+    if params.dry_run {
+        return match service
+            .dry_run_policy(policy.into_inner(), expected_version)
+            .await
+        {
+            Ok(dry_run) => Ok(HttpResponse::Ok().json(dry_run)),
+            Err(Error::ConfigVersionConflict) => Ok(version_conflict()),
+            Err(err) => Err(err),
+        };
+    }
+
+    match service
+        .patch_policy(policy.into_inner(), &name, expected_version)
+        .await
+    {
+        Ok(config) => Ok(HttpResponse::Ok()
+            .insert_header(config_etag(&config))
+            .json(config.webauthn)),
+        Err(Error::ConfigVersionConflict) => Ok(version_conflict()),
+        Err(err) => Err(err),
+    }
 }
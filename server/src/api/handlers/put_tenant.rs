@@ -0,0 +1,45 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+
+use crate::{
+    api::{authenticated_session, require_default_tenant},
+    config::AppConfigBuilder,
+    errors::Error,
+    TenantServices,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PutTenantParams {
+    /// A registered user of the tenant to grant the admin role
+    pub admin: Option<String>,
+}
+
+/// Create a tenant, or change its hosts and policy.  A new tenant starts
+/// from the default config.
+pub async fn put_tenant(
+    path: web::Path<(String,)>,
+    service: TenantServices,
+    builder: web::Json<AppConfigBuilder>,
+    params: web::Query<PutTenantParams>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    if let Err(response) = require_default_tenant(&service) {
+        return Ok(response);
+    }
+    let (_, name) = match authenticated_session(&service, &req).await {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+    let (tenant,) = path.into_inner();
+    log::trace!("Put Tenant Request: {} - {:?}", &tenant, &builder);
+
+    let config = service
+        .put_tenant(
+            &tenant,
+            builder.into_inner(),
+            params.admin.as_deref(),
+            &name,
+        )
+        .await?;
+    Ok(HttpResponse::Ok().json(config))
+}
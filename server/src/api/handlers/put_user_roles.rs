@@ -0,0 +1,22 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{errors::Error, services::Role, TenantServices};
+
+/// Replace the roles granted to a user.
+pub async fn put_user_roles(
+    path: web::Path<(String,)>,
+    service: TenantServices,
+    roles: web::Json<Vec<Role>>,
+) -> Result<HttpResponse, Error> {
+    let (name,) = path.into_inner();
+    log::trace!("Put User Roles Request: {} - {:?}", &name, &roles);
+
+    match service.put_user_roles(&name, &roles).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(roles.into_inner())),
+        Err(Error::NotFound) => {
+            Ok(HttpResponse::NotFound()
+                .body(format!(r#"{{"message": "User not found: {}"}}"#, &name)))
+        }
+        Err(err) => Err(err),
+    }
+}
@@ -1,8 +1,13 @@
-use actix_web::{web, HttpResponse};
+use actix_web::HttpResponse;
 
-use crate::{errors::Error, DataServices};
+use crate::{api::require_default_tenant, errors::Error, TenantServices};
 
-pub async fn refresh_mds(service: web::Data<DataServices>) -> Result<HttpResponse, Error> {
+/// The MDS is shared by all tenants, so only admins of the default tenant
+/// maintain it.
+pub async fn refresh_mds(service: TenantServices) -> Result<HttpResponse, Error> {
+    if let Err(response) = require_default_tenant(&service) {
+        return Ok(response);
+    }
     log::trace!("Fetching FIDO MDS");
     service.refresh_mds().await?;
 
@@ -0,0 +1,40 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    api::{authenticated_session, config_etag, if_match_version, version_conflict},
+    errors::Error,
+    TenantServices,
+};
+
+/// Restore the config of an earlier version, as a new version
+pub async fn rollback_policy(
+    path: web::Path<(u64,)>,
+    service: TenantServices,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (version,) = path.into_inner();
+    log::trace!("Rollback Policy Request: {}", version);
+    let (_, name) = match authenticated_session(&service, &req).await {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+    let expected_version = match if_match_version(&req) {
+        Ok(version) => version,
+        Err(response) => return Ok(response),
+    };
+
+    match service
+        .rollback_config(version, &name, expected_version)
+        .await
+    {
+        Ok(config) => Ok(HttpResponse::Ok()
+            .insert_header(config_etag(&config))
+            .json(config.webauthn)),
+        Err(Error::ConfigVersionConflict) => Ok(version_conflict()),
+        Err(Error::NotFound) => Ok(HttpResponse::NotFound().body(format!(
+            r#"{{"message": "Policy version not found: {}"}}"#,
+            version
+        ))),
+        Err(err) => Err(err),
+    }
+}
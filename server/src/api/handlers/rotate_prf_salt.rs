@@ -0,0 +1,35 @@
+/// Start rotating the PRF salt of one of the session user's credentials.
+/// The new salt is returned as `second`.  Once the client has re-encrypted
+/// its data with the new key, it commits the rotation.
+///
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    api::owned_credential, errors::Error, webauthn::model::AuthenticationExtensionsPrfValues,
+    TenantServices,
+};
+
+pub async fn rotate_prf_salt(
+    path: web::Path<(String,)>,
+    service: TenantServices,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (id,) = path.into_inner();
+    log::trace!("Rotate PRF Salt Request: {}", &id);
+
+    let cred = match owned_credential(&service, &req, &id).await {
+        Ok(cred) => cred,
+        Err(response) => return Ok(response),
+    };
+
+    match service.rotate_prf_salt(&cred).await {
+        Ok(cred) => {
+            let values = AuthenticationExtensionsPrfValues::for_credential(&cred);
+            Ok(HttpResponse::Ok().json(values))
+        }
+        Err(Error::PrfError(message)) => {
+            Ok(HttpResponse::Conflict().json(format!(r#"{{"message": "{}"}}"#, message)))
+        }
+        Err(err) => Err(err),
+    }
+}
@@ -1,11 +1,14 @@
 use actix_web::{web, HttpResponse};
 
-use crate::{errors::Error, DataServices};
+use crate::{api::require_default_tenant, errors::Error, TenantServices};
 
 pub async fn search_mds(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     request: web::Json<serde_json::Map<String, serde_json::Value>>,
 ) -> Result<HttpResponse, Error> {
+    if let Err(response) = require_default_tenant(&service) {
+        return Ok(response);
+    }
     log::trace!("Search FIDO MDS");
     let result = service.search_mds(&request).await?;
 
@@ -1,8 +1,14 @@
 /// The api module provides routes and handlers for all the non-WebAuthn functions, such as
 /// User and credential CRUD routines.
 ///
+pub use authenticated::*;
 pub use handlers::*;
+pub use require_admin::{require_default_tenant, RequireAdmin};
 pub use routes::*;
+pub use versioning::*;
 
+pub mod authenticated;
 pub mod handlers;
+pub mod require_admin;
 pub mod routes;
+pub mod versioning;
@@ -0,0 +1,146 @@
+//! Middleware guarding the admin scope.
+//!
+//! A request is let through only if the session named in the X-SESSION header
+//! has completed an assertion for the request's tenant, and the session user
+//! holds [Role::Admin](crate::services::Role::Admin).  If `ADMIN_MAX_AUTH_AGE`
+//! is set, the last user verification must also be recent enough.  See
+//! [AdminConfig](crate::config::AdminConfig).  If the policy requires
+//! device-bound credentials, the session must have been authenticated with
+//! one.
+//!
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web, HttpRequest, HttpResponse,
+};
+use chrono::{Duration, Utc};
+use futures::future::LocalBoxFuture;
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
+
+use super::authenticated_session;
+use crate::{config::DEFAULT_TENANT, webauthn::model::DeviceBoundRequirement, DataServices};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequireAdmin;
+
+impl<S, B> Transform<S, ServiceRequest> for RequireAdmin
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = RequireAdminMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireAdminMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequireAdminMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireAdminMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let data = req.app_data::<web::Data<DataServices>>().cloned();
+            let result = match data {
+                Some(data) => match data.for_request(req.request()).await {
+                    Ok(service) => authorize(&service, req.request()).await,
+                    Err(err) => Err(HttpResponse::from_error(err)),
+                },
+                None => Err(HttpResponse::InternalServerError()
+                    .json(r#"{ "message": "Services not available" }"#)),
+            };
+
+            match result {
+                Ok(()) => {
+                    let res = service.call(req).await?;
+                    Ok(res.map_into_left_body())
+                }
+                Err(response) => Ok(req.into_response(response).map_into_right_body()),
+            }
+        })
+    }
+}
+
+/// Tenants are managed by the admins of the default tenant only.  Returns the
+/// response to send otherwise.
+#[allow(clippy::result_large_err)]
+pub fn require_default_tenant(service: &DataServices) -> Result<(), HttpResponse> {
+    if service.tenant != DEFAULT_TENANT {
+        log::trace!("Tenant admin request from tenant: {}", &service.tenant);
+        return Err(HttpResponse::Forbidden()
+            .json(r#"{ "message": "Tenants are managed from the default tenant" }"#));
+    }
+    Ok(())
+}
+
+/// Returns the response to send if the request is not allowed.
+async fn authorize(service: &DataServices, req: &HttpRequest) -> Result<(), HttpResponse> {
+    let (session, name) = authenticated_session(service, req).await?;
+
+    match service.get_stored_user(&name).await {
+        Ok(Some(user)) if user.is_admin() => {}
+        Ok(_) => {
+            log::trace!("User is not an admin: {}", &name);
+            return Err(HttpResponse::Forbidden().json(r#"{ "message": "Admin role required" }"#));
+        }
+        Err(err) => {
+            log::trace!("Failed fetching admin user: {}", err);
+            return Err(
+                HttpResponse::InternalServerError().json(r#"{ "message": "Error getting user" }"#)
+            );
+        }
+    }
+
+    // The policy may ask for admin sessions to come from a device-bound credential
+    let config = service.get_config().await.map_err(|_| {
+        HttpResponse::InternalServerError().json(r#"{ "message": "Error getting config" }"#)
+    })?;
+    if config.webauthn.rp.device_bound != DeviceBoundRequirement::None
+        && session.as_str("device_bound").ok().as_deref() != Some("true")
+    {
+        log::trace!(
+            "Admin session is not from a device-bound credential: {}",
+            &name
+        );
+        return Err(
+            HttpResponse::Forbidden().json(r#"{ "message": "Device-bound credential required" }"#)
+        );
+    }
+
+    if let Some(max_age) = service.admin.max_auth_age {
+        let fresh = match session.as_time("user_verified_at") {
+            Ok(verified_at) => Utc::now() - verified_at <= Duration::seconds(max_age),
+            Err(_) => false,
+        };
+        if !fresh {
+            log::trace!("Admin user verification is too old: {}", &name);
+            return Err(HttpResponse::Forbidden()
+                .json(r#"{ "message": "Fresh user verification required" }"#));
+        }
+    }
+
+    Ok(())
+}
@@ -1,27 +1,73 @@
-use super::handlers;
+use super::{handlers, RequireAdmin};
 use actix_web::web;
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("api")
-            .service(web::resource("/mds/refresh").route(web::get().to(handlers::refresh_mds)))
-            .service(web::resource("/mds/search").route(web::post().to(handlers::search_mds)))
             .service(
-                web::resource("/policy")
-                    .route(web::get().to(handlers::get_policy))
-                    .route(web::patch().to(handlers::patch_policy)),
+                web::scope("/admin")
+                    .wrap(RequireAdmin)
+                    .service(
+                        web::resource("/mds/refresh").route(web::get().to(handlers::refresh_mds)),
+                    )
+                    .service(
+                        web::resource("/mds/search").route(web::post().to(handlers::search_mds)),
+                    )
+                    .service(
+                        web::resource("/policy").route(web::patch().to(handlers::patch_policy)),
+                    )
+                    .service(
+                        web::resource("/policy/versions")
+                            .route(web::get().to(handlers::get_policy_versions)),
+                    )
+                    .service(
+                        web::resource("/policy/versions/{version}/rollback")
+                            .route(web::post().to(handlers::rollback_policy)),
+                    )
+                    .service(web::resource("/tenants").route(web::get().to(handlers::get_tenants)))
+                    .service(
+                        web::resource("/tenants/{name}")
+                            .route(web::put().to(handlers::put_tenant))
+                            .route(web::delete().to(handlers::delete_tenant)),
+                    )
+                    .service(web::resource("/users").route(web::get().to(handlers::get_users)))
+                    .service(
+                        web::resource("/users/{name}")
+                            .route(web::delete().to(handlers::delete_user)),
+                    )
+                    .service(
+                        web::resource("/users/{name}/roles")
+                            .route(web::put().to(handlers::put_user_roles)),
+                    ),
             )
+            .service(web::resource("/policy").route(web::get().to(handlers::get_policy)))
+            .service(web::resource("/session").route(web::get().to(handlers::get_session)))
             .service(
                 web::resource("/credentials/user")
                     .route(web::post().to(handlers::get_user_credentials)),
             )
             .service(
-                web::resource("/users")
-                    .route(web::get().to(handlers::get_users))
-                    .route(web::post().to(handlers::get_user)),
+                web::resource("/credentials/{id}/prf")
+                    .route(web::get().to(handlers::get_prf_salts)),
             )
+            .service(
+                web::resource("/credentials/{id}/prf/rotate")
+                    .route(web::post().to(handlers::rotate_prf_salt)),
+            )
+            .service(
+                web::resource("/credentials/{id}/prf/commit")
+                    .route(web::post().to(handlers::commit_prf_salt)),
+            )
+            .service(
+                web::resource("/credentials/{id}/large-blob")
+                    .route(web::post().to(handlers::large_blob_challenge)),
+            )
+            .service(web::resource("/users").route(web::post().to(handlers::get_user)))
             .service(web::resource("/users/check").route(web::post().to(handlers::check_user)))
-            .service(web::resource("/users/delete").route(web::post().to(handlers::delete_user)))
             .service(web::resource("/users/logout").route(web::post().to(handlers::logout_user))),
+    )
+    .service(
+        web::resource("/.well-known/webauthn")
+            .route(web::get().to(handlers::get_well_known_webauthn)),
     );
 }
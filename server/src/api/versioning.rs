@@ -0,0 +1,44 @@
+//! Optimistic concurrency for config changes.
+//!
+//! Responses with a config carry its version as the `ETag`.  A change sent
+//! with `If-Match` is refused with 412 if the config has another version by
+//! then.  Without `If-Match` the change applies to the current version.
+//!
+use actix_web::{
+    http::header::{self, ETag, EntityTag},
+    HttpRequest, HttpResponse,
+};
+
+use crate::config::AppConfig;
+
+pub fn config_etag(config: &AppConfig) -> ETag {
+    ETag(EntityTag::new_strong(config.version.to_string()))
+}
+
+/// The version in the `If-Match` header, if there is one.  Returns the
+/// response to send if the header is not a version.
+#[allow(clippy::result_large_err)]
+pub fn if_match_version(req: &HttpRequest) -> Result<Option<u64>, HttpResponse> {
+    let value = match req.headers().get(header::IF_MATCH) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let tag = value.to_str().unwrap_or_default().trim();
+    if tag == "*" {
+        return Ok(None);
+    }
+    tag.trim_start_matches("W/")
+        .trim_matches('"')
+        .parse::<u64>()
+        .map(Some)
+        .map_err(|_| {
+            log::trace!("Bad If-Match header: {}", tag);
+            HttpResponse::BadRequest().json(r#"{ "message": "If-Match must be a config version" }"#)
+        })
+}
+
+/// The response to a change refused by [if_match_version]
+pub fn version_conflict() -> HttpResponse {
+    HttpResponse::PreconditionFailed()
+        .json(r#"{ "message": "The config has changed, reload and try again" }"#)
+}
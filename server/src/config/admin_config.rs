@@ -0,0 +1,57 @@
+/// Settings for the admin API.  These are deployment settings rather than
+/// WebAuthn policy, so they are only read from the env and are never stored.
+///
+/// - `ADMIN_BOOTSTRAP_USER`: name of the user that is granted the admin role
+///   when there are no admins yet.  The user is promoted at startup if it
+///   already exists, or as soon as it registers a credential.
+/// - `ADMIN_BOOTSTRAP_TOKEN`: secret that must be sent in the
+///   `X-BOOTSTRAP-TOKEN` header to register the bootstrap user.  Without it
+///   the bootstrap user can only be promoted at startup.
+/// - `ADMIN_MAX_AUTH_AGE`: if set, the number of seconds since the last user
+///   verified assertion after which admin requests are refused.  Leave unset
+///   to accept any authenticated admin session.
+///
+use std::env;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AdminConfig {
+    pub bootstrap_user: Option<String>,
+    pub bootstrap_token: Option<String>,
+    pub max_auth_age: Option<i64>,
+}
+
+impl AdminConfig {
+    pub fn from_env() -> Self {
+        Self {
+            bootstrap_user: env_bootstrap_user(),
+            bootstrap_token: env_bootstrap_token(),
+            max_auth_age: env_max_auth_age(),
+        }
+    }
+}
+
+fn env_bootstrap_user() -> Option<String> {
+    match env::var("ADMIN_BOOTSTRAP_USER") {
+        Ok(name) if !name.is_empty() => Some(name),
+        _ => None,
+    }
+}
+
+fn env_bootstrap_token() -> Option<String> {
+    match env::var("ADMIN_BOOTSTRAP_TOKEN") {
+        Ok(token) if !token.is_empty() => Some(token),
+        _ => None,
+    }
+}
+
+fn env_max_auth_age() -> Option<i64> {
+    if let Ok(result) = env::var("ADMIN_MAX_AUTH_AGE") {
+        Some(
+            result
+                .parse::<i64>()
+                .expect("Failed to parse ADMIN_MAX_AUTH_AGE from env"),
+        )
+    } else {
+        None
+    }
+}
@@ -13,11 +13,50 @@ use url::Url;
 
 use crate::webauthn::model::*;
 
+/// The tenant of configs stored before tenants existed, and of requests that
+/// no other tenant claims
+pub const DEFAULT_TENANT: &str = "default";
+
+fn default_tenant() -> String {
+    DEFAULT_TENANT.to_string()
+}
+
+/// Tenant names are used in collection names and paths, so they are limited
+/// to lowercase letters, digits and `-`.
+pub fn is_valid_tenant(tenant: &str) -> bool {
+    !tenant.is_empty()
+        && tenant.len() <= 32
+        && tenant != "system"
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// The config of one tenant.  Requests are routed to a tenant by one of its
+/// `hosts`, or by a `/t/{tenant}` path prefix.
+///
+/// Each change to the config increments `version`.  Configs stored before
+/// versions existed are version 0.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AppConfig {
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
     pub webauthn: WebauthnPolicy,
 }
 
+/// Changes to the config of a tenant.  A new tenant starts from the default
+/// config.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfigBuilder {
+    pub hosts: Option<Vec<String>>,
+    pub webauthn: Option<WebauthnPolicyBuilder>,
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         AppConfig::new()
@@ -27,6 +66,9 @@ impl Default for AppConfig {
 impl AppConfig {
     pub fn new() -> AppConfig {
         Self {
+            tenant: default_tenant(),
+            version: 0,
+            hosts: vec![],
             webauthn: default_webauthn_policy(),
         }
         // This is where the default WebauthnPolicy gets created
@@ -43,13 +85,17 @@ fn env_transports() -> Option<Vec<AuthenticatorTransport>> {
     }
 }
 
-fn env_alg() -> COSEAlgorithm {
+/// A single algorithm, or a JSON list of them in order of preference
+fn env_algs() -> Vec<COSEAlgorithm> {
     if let Ok(result) = env::var("WEBAUTHN_ALG") {
-        let alg: COSEAlgorithm =
-            serde_json::from_str(&result).expect("Failed to parse WEBAUTHN_ALG from env");
-        alg
+        let mut deserializer = serde_json::Deserializer::from_str(&result);
+        deserialize_algs(&mut deserializer).expect("Failed to parse WEBAUTHN_ALG from env")
     } else {
-        COSEAlgorithm::ES256
+        vec![
+            COSEAlgorithm::ES256,
+            COSEAlgorithm::EdDSA,
+            COSEAlgorithm::RS256,
+        ]
     }
 }
 
@@ -83,6 +129,17 @@ fn env_user_verification() -> UserVerificationRequirement {
     }
 }
 
+/// Defaults to the registration setting
+fn env_authentication_user_verification() -> UserVerificationRequirement {
+    if let Ok(result) = env::var("WEBAUTHN_AUTHENTICATION_USER_VERIFICATION") {
+        let user_verification: UserVerificationRequirement = serde_json::from_str(&result)
+            .expect("Failed to parse WEBAUTHN_AUTHENTICATION_USER_VERIFICATION from env");
+        user_verification
+    } else {
+        env_user_verification()
+    }
+}
+
 fn env_attestation() -> AttestationConveyancePreference {
     if let Ok(result) = env::var("WEBAUTHN_CONVEYANCE_PREFERENCE") {
         let attestation: AttestationConveyancePreference = serde_json::from_str(&result)
@@ -103,6 +160,27 @@ fn env_timeout() -> usize {
     }
 }
 
+/// Defaults to the registration setting
+fn env_authentication_timeout() -> usize {
+    if let Ok(result) = env::var("WEBAUTHN_AUTHENTICATION_TIMEOUT") {
+        result
+            .parse::<usize>()
+            .expect("Failed to parse WEBAUTHN_AUTHENTICATION_TIMEOUT from env")
+    } else {
+        env_timeout()
+    }
+}
+
+fn env_challenge_grace() -> usize {
+    if let Ok(result) = env::var("WEBAUTHN_CHALLENGE_GRACE") {
+        result
+            .parse::<usize>()
+            .expect("Failed to parse WEBAUTHN_CHALLENGE_GRACE from env")
+    } else {
+        DEFAULT_CHALLENGE_GRACE
+    }
+}
+
 fn env_validate_sign_count() -> bool {
     if let Ok(result) = env::var("WEBAUTHN_VALIDATE_SIGN_COUNT") {
         result
@@ -113,11 +191,107 @@ fn env_validate_sign_count() -> bool {
     }
 }
 
-fn env_origin() -> Url {
-    if let Ok(url) = env::var("WEBAUTHN_ORIGIN") {
-        Url::try_from(url.as_str()).expect("Failed to parse WEBAUTHN_ORIGIN from env")
+fn env_device_bound() -> DeviceBoundRequirement {
+    if let Ok(result) = env::var("WEBAUTHN_DEVICE_BOUND") {
+        let device_bound: DeviceBoundRequirement =
+            serde_json::from_str(&result).expect("Failed to parse WEBAUTHN_DEVICE_BOUND from env");
+        device_bound
+    } else {
+        DeviceBoundRequirement::None
+    }
+}
+
+fn env_warn_not_backed_up() -> bool {
+    if let Ok(result) = env::var("WEBAUTHN_WARN_NOT_BACKED_UP") {
+        result
+            .parse::<bool>()
+            .expect("Failed to parse WEBAUTHN_WARN_NOT_BACKED_UP from env")
+    } else {
+        false
+    }
+}
+
+fn env_prf() -> bool {
+    if let Ok(result) = env::var("WEBAUTHN_PRF") {
+        result
+            .parse::<bool>()
+            .expect("Failed to parse WEBAUTHN_PRF from env")
     } else {
-        Url::try_from("http://localhost:3000").expect("Url parse error")
+        false
+    }
+}
+
+fn env_large_blob() -> LargeBlobRequirement {
+    if let Ok(result) = env::var("WEBAUTHN_LARGE_BLOB") {
+        let large_blob: LargeBlobRequirement =
+            serde_json::from_str(&result).expect("Failed to parse WEBAUTHN_LARGE_BLOB from env");
+        large_blob
+    } else {
+        LargeBlobRequirement::None
+    }
+}
+
+fn env_cred_protect() -> CredentialProtectionPolicy {
+    if let Ok(result) = env::var("WEBAUTHN_CRED_PROTECT") {
+        let cred_protect: CredentialProtectionPolicy =
+            serde_json::from_str(&result).expect("Failed to parse WEBAUTHN_CRED_PROTECT from env");
+        cred_protect
+    } else {
+        CredentialProtectionPolicy::UserVerificationOptional
+    }
+}
+
+fn env_min_pin_length() -> u32 {
+    if let Ok(result) = env::var("WEBAUTHN_MIN_PIN_LENGTH") {
+        result
+            .parse::<u32>()
+            .expect("Failed to parse WEBAUTHN_MIN_PIN_LENGTH from env")
+    } else {
+        0
+    }
+}
+
+/// A single origin, or a JSON list of origins
+fn env_origins() -> Vec<AllowedOrigin> {
+    if let Ok(result) = env::var("WEBAUTHN_ORIGIN") {
+        if result.trim_start().starts_with('[') {
+            serde_json::from_str(&result).expect("Failed to parse WEBAUTHN_ORIGIN from env")
+        } else {
+            let origin =
+                AllowedOrigin::try_from(result).expect("Failed to parse WEBAUTHN_ORIGIN from env");
+            vec![origin]
+        }
+    } else {
+        let origin = Url::parse("http://localhost:3000").expect("Url parse error");
+        vec![AllowedOrigin::Web(origin)]
+    }
+}
+
+/// A JSON list of related origins
+fn env_related_origins() -> Vec<AllowedOrigin> {
+    if let Ok(result) = env::var("WEBAUTHN_RELATED_ORIGINS") {
+        serde_json::from_str(&result).expect("Failed to parse WEBAUTHN_RELATED_ORIGINS from env")
+    } else {
+        vec![]
+    }
+}
+
+fn env_cross_origin() -> bool {
+    if let Ok(result) = env::var("WEBAUTHN_CROSS_ORIGIN") {
+        result
+            .parse::<bool>()
+            .expect("Failed to parse WEBAUTHN_CROSS_ORIGIN from env")
+    } else {
+        false
+    }
+}
+
+/// A JSON list of the sites that may embed a cross-origin ceremony
+fn env_top_origins() -> Vec<AllowedOrigin> {
+    if let Ok(result) = env::var("WEBAUTHN_TOP_ORIGINS") {
+        serde_json::from_str(&result).expect("Failed to parse WEBAUTHN_TOP_ORIGINS from env")
+    } else {
+        vec![]
     }
 }
 
@@ -126,31 +300,64 @@ fn default_webauthn_policy() -> WebauthnPolicy {
     let rp_name = env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "swankymutt".to_string());
     let key_type = PublicKeyCredentialType::PublicKey;
 
-    let origin = env_origin();
+    let origins = env_origins();
+    let related_origins = env_related_origins();
+    let cross_origin = env_cross_origin();
+    let top_origins = env_top_origins();
     let authenticator_transports = env_transports();
-    let alg = env_alg();
+    let algs = env_algs();
     let authenticator_attachment = env_authenticator_attachment();
     let resident_key = env_resident_key();
     let user_verification = env_user_verification();
+    let authentication_user_verification = env_authentication_user_verification();
     let attestation = env_attestation();
     let timeout = env_timeout();
+    let authentication_timeout = env_authentication_timeout();
+    let challenge_grace = env_challenge_grace();
     let validate_sign_count = env_validate_sign_count();
+    let device_bound = env_device_bound();
+    let warn_not_backed_up = env_warn_not_backed_up();
+    let prf = env_prf();
+    let large_blob = env_large_blob();
+    let cred_protect = env_cred_protect();
+    let min_pin_length = env_min_pin_length();
+    let app_id = env::var("WEBAUTHN_APP_ID").ok();
 
-    // The builder will fail if every policy element is not explicitly set.  There
-    // are no defaults inside [WebAuthnPolicy] or the builder itself.
-    WebauthnPolicyBuilder::default()
-        .with_origin(origin)
-        .with_rp_id(rp_id)
-        .with_rp_name(rp_name)
+    let rp = RelyingPartyPolicyBuilder::default()
+        .with_origins(origins)
+        .with_related_origins(related_origins)
+        .with_cross_origin(cross_origin)
+        .with_top_origins(top_origins)
+        .with_id(rp_id)
+        .with_name(rp_name)
+        .with_challenge_grace(challenge_grace)
+        .with_device_bound(device_bound)
+        .with_warn_not_backed_up(warn_not_backed_up)
+        .with_prf(prf)
+        .with_app_id(app_id);
+    let registration = RegistrationPolicyBuilder::default()
         .with_key_type(key_type)
-        .with_alg(alg)
+        .with_algs(algs)
         .with_authenticator_attachment(authenticator_attachment)
         .with_resident_key(resident_key)
         .with_user_verification(user_verification)
         .with_attestation(attestation)
         .with_timeout(timeout)
-        .with_validate_sign_count(validate_sign_count)
         .with_authenticator_transports(authenticator_transports)
+        .with_large_blob(large_blob)
+        .with_cred_protect(cred_protect)
+        .with_min_pin_length(min_pin_length);
+    let authentication = AuthenticationPolicyBuilder::default()
+        .with_user_verification(authentication_user_verification)
+        .with_timeout(authentication_timeout)
+        .with_validate_sign_count(validate_sign_count);
+
+    // The builder will fail if every policy element is not explicitly set.  There
+    // are no defaults inside [WebAuthnPolicy] or the builder itself.
+    WebauthnPolicyBuilder::default()
+        .with_rp(rp)
+        .with_registration(registration)
+        .with_authentication(authentication)
         .build()
         .expect("Failed to build WebauthnPolicy.  Likely due to missing policy statements")
 }
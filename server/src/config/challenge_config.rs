@@ -0,0 +1,73 @@
+/// Selects how challenges are tracked.  Like [AdminConfig](super::AdminConfig),
+/// this is a deployment setting and is only read from the env.
+///
+/// - `WEBAUTHN_CHALLENGE_MODE`: `stored` (the default) keeps every challenge in
+///   the `webauthn_challenge` collection.  `stateless` issues HMAC signed
+///   challenge tokens that are verified without the database.  Only the nonces
+///   of used tokens are kept, in Redis, until the tokens expire.
+/// - `WEBAUTHN_CHALLENGE_KEY`: base64url HMAC key of at least 32 bytes.
+///   Required for `stateless`.  Every server instance must share the key.
+///
+use serde::{Deserialize, Serialize};
+use std::env;
+
+use crate::utils::from_b64;
+
+pub const MIN_CHALLENGE_KEY_LEN: usize = 32;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChallengeMode {
+    Stored,
+    Stateless,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChallengeConfig {
+    pub mode: ChallengeMode,
+    pub key: Option<Vec<u8>>,
+}
+
+impl Default for ChallengeConfig {
+    fn default() -> Self {
+        Self {
+            mode: ChallengeMode::Stored,
+            key: None,
+        }
+    }
+}
+
+impl ChallengeConfig {
+    pub fn from_env() -> Self {
+        let mode = env_challenge_mode();
+        let key = env_challenge_key();
+        if mode == ChallengeMode::Stateless && key.is_none() {
+            panic!("WEBAUTHN_CHALLENGE_KEY is required for stateless challenges");
+        }
+        Self { mode, key }
+    }
+}
+
+fn env_challenge_mode() -> ChallengeMode {
+    if let Ok(result) = env::var("WEBAUTHN_CHALLENGE_MODE") {
+        serde_json::from_value(serde_json::Value::String(result))
+            .expect("Failed to parse WEBAUTHN_CHALLENGE_MODE from env")
+    } else {
+        ChallengeMode::Stored
+    }
+}
+
+fn env_challenge_key() -> Option<Vec<u8>> {
+    if let Ok(result) = env::var("WEBAUTHN_CHALLENGE_KEY") {
+        let key = from_b64(&result).expect("Failed to parse WEBAUTHN_CHALLENGE_KEY from env");
+        if key.len() < MIN_CHALLENGE_KEY_LEN {
+            panic!(
+                "WEBAUTHN_CHALLENGE_KEY must be at least {} bytes",
+                MIN_CHALLENGE_KEY_LEN
+            );
+        }
+        Some(key)
+    } else {
+        None
+    }
+}
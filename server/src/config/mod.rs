@@ -1,4 +1,8 @@
 /// Storage for App policy and configuration settings
 ///
+pub use admin_config::*;
 pub use app_config::*;
+pub use challenge_config::*;
+pub mod admin_config;
 pub mod app_config;
+pub mod challenge_config;
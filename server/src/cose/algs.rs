@@ -6,15 +6,49 @@ use openssl::ec::{EcGroup, EcKey};
 use openssl::hash::MessageDigest;
 use openssl::nid::Nid;
 use openssl::pkey::PKey;
-use openssl::sign::Verifier;
+use openssl::rsa::Padding;
+use openssl::sign::{RsaPssSaltlen, Verifier};
 
 // Signing algotihtms
 pub const ES256: i32 = -7;
 pub const ES384: i32 = -35;
 pub const ES512: i32 = -36;
+pub const ES256K: i32 = -47;
 pub const EDDSA: i32 = -8;
-pub const SIGNING_ALGS: [i32; 4] = [ES256, ES384, ES512, EDDSA];
-pub const SIGNING_ALGS_NAMES: [&str; 4] = ["ES256", "ES384", "ES512", "EDDSA"];
+pub const PS256: i32 = -37;
+pub const PS384: i32 = -38;
+pub const PS512: i32 = -39;
+pub const RS256: i32 = -257;
+pub const RS384: i32 = -258;
+pub const RS512: i32 = -259;
+pub const ML_DSA_44: i32 = -48;
+pub const ML_DSA_65: i32 = -49;
+pub const ML_DSA_87: i32 = -50;
+pub const SIGNING_ALGS: [i32; 14] = [
+    ES256, ES384, ES512, ES256K, EDDSA, PS256, PS384, PS512, RS256, RS384, RS512, ML_DSA_44,
+    ML_DSA_65, ML_DSA_87,
+];
+pub const SIGNING_ALGS_NAMES: [&str; 14] = [
+    "ES256",
+    "ES384",
+    "ES512",
+    "ES256K",
+    "EDDSA",
+    "PS256",
+    "PS384",
+    "PS512",
+    "RS256",
+    "RS384",
+    "RS512",
+    "ML-DSA-44",
+    "ML-DSA-65",
+    "ML-DSA-87",
+];
+pub const RSA_SIGNING_ALGS: [i32; 6] = [PS256, PS384, PS512, RS256, RS384, RS512];
+pub const ML_DSA_SIGNING_ALGS: [i32; 3] = [ML_DSA_44, ML_DSA_65, ML_DSA_87];
+
+/// Smallest RSA modulus accepted for signatures, in bits
+pub const RSA_MIN_BITS: u32 = 2048;
 
 // Encryption algorithms
 pub const A128GCM: i32 = 1;
@@ -186,10 +220,18 @@ pub fn verify(
     } else if alg == ES512 {
         group = EcGroup::from_curve_name(Nid::SECP521R1)?;
         message_digest = MessageDigest::sha512();
+    } else if alg == ES256K {
+        group = EcGroup::from_curve_name(Nid::SECP256K1)?;
+        message_digest = MessageDigest::sha256();
     } else if alg == EDDSA {
+        // Ed25519 or Ed448, as given by the key's DER prefix
         let ec_public_key = PKey::public_key_from_der(key.as_slice())?;
         let mut verifier = Verifier::new(MessageDigest::null(), &ec_public_key)?;
         return Ok(verifier.verify_oneshot(signature, content)?);
+    } else if RSA_SIGNING_ALGS.contains(&alg) {
+        return verify_rsa(alg, key, content, signature);
+    } else if ML_DSA_SIGNING_ALGS.contains(&alg) {
+        return verify_ml_dsa(alg, key, content, signature);
     } else {
         return Err(CoseError::InvalidAlgorithm());
     }
@@ -200,3 +242,186 @@ pub fn verify(
     verifier.update(content)?;
     Ok(verifier.verify(signature)?)
 }
+
+/// Verify an RSASSA-PKCS1-v1_5 (RS*) or RSASSA-PSS (PS*) signature.  The key
+/// is a DER encoded SubjectPublicKeyInfo, as returned by
+/// [CoseKey::get_pub_key](super::CoseKey::get_pub_key).  PSS uses MGF1 with the
+/// same digest, and a salt as long as the digest.
+fn verify_rsa(alg: i32, key: &[u8], content: &[u8], signature: &[u8]) -> CoseResultWithRet<bool> {
+    let message_digest = match alg {
+        RS256 | PS256 => MessageDigest::sha256(),
+        RS384 | PS384 => MessageDigest::sha384(),
+        RS512 | PS512 => MessageDigest::sha512(),
+        _ => return Err(CoseError::InvalidAlgorithm()),
+    };
+    let public_key = PKey::public_key_from_der(key)?;
+    if public_key.rsa()?.size() * 8 < RSA_MIN_BITS {
+        return Err(CoseError::InvalidParameter("RSA key size".to_string()));
+    }
+    let mut verifier = Verifier::new(message_digest, &public_key)?;
+    if [PS256, PS384, PS512].contains(&alg) {
+        verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+        verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+        verifier.set_rsa_mgf1_md(message_digest)?;
+    }
+    verifier.update(content)?;
+    Ok(verifier.verify(signature)?)
+}
+
+/// Verify a pure ML-DSA signature, with an empty context.  The key is the
+/// encoded public key from an AKP cose-key.  Only available with the `ml-dsa`
+/// feature.
+#[cfg(feature = "ml-dsa")]
+fn verify_ml_dsa(
+    alg: i32,
+    key: &[u8],
+    content: &[u8],
+    signature: &[u8],
+) -> CoseResultWithRet<bool> {
+    use ml_dsa::{MlDsa44, MlDsa65, MlDsa87};
+
+    match alg {
+        ML_DSA_44 => verify_ml_dsa_with::<MlDsa44>(key, content, signature),
+        ML_DSA_65 => verify_ml_dsa_with::<MlDsa65>(key, content, signature),
+        ML_DSA_87 => verify_ml_dsa_with::<MlDsa87>(key, content, signature),
+        _ => Err(CoseError::InvalidAlgorithm()),
+    }
+}
+
+#[cfg(feature = "ml-dsa")]
+fn verify_ml_dsa_with<P: ml_dsa::MlDsaParams>(
+    key: &[u8],
+    content: &[u8],
+    signature: &[u8],
+) -> CoseResultWithRet<bool> {
+    use ml_dsa::{EncodedSignature, EncodedVerifyingKey, Signature, VerifyingKey};
+
+    let key = EncodedVerifyingKey::<P>::try_from(key)
+        .map_err(|_| CoseError::InvalidParameter("pub".to_string()))?;
+    let signature = match EncodedSignature::<P>::try_from(signature)
+        .ok()
+        .and_then(|signature| Signature::<P>::decode(&signature))
+    {
+        Some(signature) => signature,
+        None => return Ok(false),
+    };
+    Ok(VerifyingKey::<P>::decode(&key).verify_with_context(content, &[], &signature))
+}
+
+#[cfg(not(feature = "ml-dsa"))]
+fn verify_ml_dsa(
+    _alg: i32,
+    _key: &[u8],
+    _content: &[u8],
+    _signature: &[u8],
+) -> CoseResultWithRet<bool> {
+    Err(CoseError::InvalidAlgorithm())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cose::CoseKey;
+
+    fn decode(value: &serde_json::Value) -> Vec<u8> {
+        base64::decode_config(value.as_str().expect("oops"), base64::URL_SAFE_NO_PAD).expect("oops")
+    }
+
+    #[test]
+    fn test_rsa_vectors() -> CoseResultWithRet<()> {
+        let json = include_str!("../../test_data/rsa-cose-vectors.json");
+        let vectors: serde_json::Value = serde_json::from_str(json).expect("oops");
+        let cose_key = CoseKey::decode_bytes(&decode(&vectors["coseKey"]))?;
+        let message = decode(&vectors["message"]);
+
+        let spki = cose_key.get_pub_key(RS256)?;
+        assert_eq!(spki, decode(&vectors["spki"]));
+
+        for (name, alg) in SIGNING_ALGS_NAMES.iter().zip(SIGNING_ALGS.iter()) {
+            if !RSA_SIGNING_ALGS.contains(alg) {
+                continue;
+            }
+            let signature = decode(&vectors["signatures"][*name]);
+            assert!(verify(*alg, &spki, &message, &signature)?, "{}", name);
+            let forged = verify(*alg, &spki, b"something else", &signature);
+            assert!(!matches!(forged, Ok(true)), "{}", name);
+        }
+
+        // PKCS#1 v1.5 and PSS signatures are not interchangeable
+        let signature = decode(&vectors["signatures"]["RS256"]);
+        assert!(!matches!(
+            verify(PS256, &spki, &message, &signature),
+            Ok(true)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_es256k_and_ed448_vectors() -> CoseResultWithRet<()> {
+        let json = include_str!("../../test_data/ec-cose-vectors.json");
+        let vectors: serde_json::Value = serde_json::from_str(json).expect("oops");
+        let message = decode(&vectors["message"]);
+
+        let cose_key = CoseKey::decode_bytes(&decode(&vectors["ES256K"]["coseKey"]))?;
+        let key = cose_key.get_pub_key(ES256K)?;
+        let signature = decode(&vectors["ES256K"]["signature"]);
+        assert!(verify(ES256K, &key, &message, &signature)?);
+        let forged = verify(ES256K, &key, b"something else", &signature);
+        assert!(!matches!(forged, Ok(true)));
+        // A secp256k1 key is not a P-256 key
+        assert!(cose_key.get_pub_key(ES256).is_err());
+
+        let cose_key = CoseKey::decode_bytes(&decode(&vectors["Ed448"]["coseKey"]))?;
+        let key = cose_key.get_pub_key(EDDSA)?;
+        assert_eq!(key, decode(&vectors["Ed448"]["spki"]));
+        let signature = decode(&vectors["Ed448"]["signature"]);
+        assert!(verify(EDDSA, &key, &message, &signature)?);
+        let forged = verify(EDDSA, &key, b"something else", &signature);
+        assert!(!matches!(forged, Ok(true)));
+        assert!(cose_key.get_pub_key(ES256K).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ml_dsa_keys() -> CoseResultWithRet<()> {
+        let json = include_str!("../../test_data/ml-dsa-cose-vectors.json");
+        let vectors: serde_json::Value = serde_json::from_str(json).expect("oops");
+
+        // Encoded public key sizes from FIPS 204
+        let algs = [
+            ("ML-DSA-44", ML_DSA_44, 1312),
+            ("ML-DSA-65", ML_DSA_65, 1952),
+            ("ML-DSA-87", ML_DSA_87, 2592),
+        ];
+        for (name, alg, len) in algs {
+            let cose_key = CoseKey::decode_bytes(&decode(&vectors["keys"][name]["coseKey"]))?;
+            assert_eq!(cose_key.kty, Some(crate::cose::keys::AKP), "{}", name);
+            assert_eq!(cose_key.get_pub_key(alg)?.len(), len, "{}", name);
+            assert!(cose_key.get_pub_key(ES256).is_err(), "{}", name);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "ml-dsa")]
+    #[test]
+    fn test_ml_dsa_vectors() -> CoseResultWithRet<()> {
+        let json = include_str!("../../test_data/ml-dsa-cose-vectors.json");
+        let vectors: serde_json::Value = serde_json::from_str(json).expect("oops");
+        let message = decode(&vectors["message"]);
+
+        let algs = [
+            ("ML-DSA-44", ML_DSA_44),
+            ("ML-DSA-65", ML_DSA_65),
+            ("ML-DSA-87", ML_DSA_87),
+        ];
+        for (name, alg) in algs {
+            let cose_key = CoseKey::decode_bytes(&decode(&vectors["keys"][name]["coseKey"]))?;
+            let key = cose_key.get_pub_key(alg)?;
+            let signature = decode(&vectors["keys"][name]["signature"]);
+            assert!(verify(alg, &key, &message, &signature)?, "{}", name);
+            let forged = verify(alg, &key, b"something else", &signature);
+            assert!(!matches!(forged, Ok(true)), "{}", name);
+        }
+        Ok(())
+    }
+}
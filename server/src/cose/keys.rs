@@ -1,5 +1,8 @@
 //! Module to encode/decode cose-keys/cose-keySet.
 use cbor::{decoder::DecodeError, types::Type, Config, Decoder, Encoder};
+use openssl::bn::BigNum;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
 use serde::{Deserialize, Serialize};
 use std::io::Cursor;
 use std::str::from_utf8;
@@ -18,13 +21,23 @@ pub const ALG: i32 = 3;
 pub const KEY_OPS: i32 = 4;
 pub const BASE_IV: i32 = 5;
 
+//RSA PARAMETERS.  These reuse the labels of crv/k and x.
+pub const N: i32 = -1;
+pub const E: i32 = -2;
+
+//AKP PARAMETERS (ML-DSA).  These reuse the labels of crv/k and x.
+pub const AKP_PUB: i32 = -1;
+pub const AKP_PRIV: i32 = -2;
+
 //KEY TYPES
 pub const OKP: i32 = 1;
 pub const EC2: i32 = 2;
+pub const RSA: i32 = 3;
 pub const SYMMETRIC: i32 = 4;
+pub const AKP: i32 = 7;
 pub const RESERVED: i32 = 0;
-pub const KTY_ALL: [i32; 4] = [RESERVED, OKP, EC2, SYMMETRIC];
-pub const KTY_NAMES: [&str; 4] = ["Reserved", "OKP", "EC2", "Symmetric"];
+pub const KTY_ALL: [i32; 6] = [RESERVED, OKP, EC2, RSA, SYMMETRIC, AKP];
+pub const KTY_NAMES: [&str; 6] = ["Reserved", "OKP", "EC2", "RSA", "Symmetric", "AKP"];
 
 //KEY OPERATIONS
 pub const KEY_OPS_SIGN: i32 = 1;
@@ -70,9 +83,17 @@ pub const X25519: i32 = 4;
 pub const X448: i32 = 5;
 pub const ED25519: i32 = 6;
 pub const ED448: i32 = 7;
-pub const CURVES_ALL: [i32; 7] = [P_256, P_384, P_521, X25519, X448, ED25519, ED448];
-pub const CURVES_NAMES: [&str; 7] = [
-    "P-256", "P-384", "P-521", "X25519", "X448", "Ed25519", "Ed448",
+pub const SECP256K1: i32 = 8;
+pub const CURVES_ALL: [i32; 8] = [P_256, P_384, P_521, X25519, X448, ED25519, ED448, SECP256K1];
+pub const CURVES_NAMES: [&str; 8] = [
+    "P-256",
+    "P-384",
+    "P-521",
+    "X25519",
+    "X448",
+    "Ed25519",
+    "Ed448",
+    "secp256k1",
 ];
 
 /// cose-key structure.
@@ -102,6 +123,15 @@ pub struct CoseKey {
     pub kid: Option<Vec<u8>>,
     /// COSE curve for OKP/EC2 keys.
     pub crv: Option<i32>,
+    /// Modulus for RSA keys.
+    #[serde(default)]
+    pub n: Option<Vec<u8>>,
+    /// Public exponent for RSA keys.
+    #[serde(default)]
+    pub e: Option<Vec<u8>>,
+    /// Public key for AKP keys.  The private key is kept in `d`.
+    #[serde(default)]
+    pub akp_pub: Option<Vec<u8>>,
 }
 
 impl Default for CoseKey {
@@ -127,6 +157,9 @@ impl CoseKey {
             k: None,
             kid: None,
             crv: None,
+            n: None,
+            e: None,
+            akp_pub: None,
         }
     }
 
@@ -195,6 +228,24 @@ impl CoseKey {
         self.k = Some(k);
     }
 
+    /// Adds the modulus to an RSA cose-key.
+    pub fn n(&mut self, n: Vec<u8>) {
+        self.reg_label(N);
+        self.n = Some(n);
+    }
+
+    /// Adds the public exponent to an RSA cose-key.
+    pub fn e(&mut self, e: Vec<u8>) {
+        self.reg_label(E);
+        self.e = Some(e);
+    }
+
+    /// Adds the public key to an AKP cose-key.
+    pub fn akp_pub(&mut self, akp_pub: Vec<u8>) {
+        self.reg_label(AKP_PUB);
+        self.akp_pub = Some(akp_pub);
+    }
+
     /// Method to encode the cose-Key.
     pub fn encode(&mut self) -> CoseResult {
         let mut e = Encoder::new(Vec::new());
@@ -260,6 +311,30 @@ impl CoseKey {
                 for x in &key_ops {
                     e.i32(*x)?;
                 }
+            } else if i == N && kty == RSA {
+                e.bytes(
+                    self.n
+                        .as_ref()
+                        .ok_or_else(|| CoseError::MissingParameter("n".to_string()))?,
+                )?;
+            } else if i == E && kty == RSA {
+                e.bytes(
+                    self.e
+                        .as_ref()
+                        .ok_or_else(|| CoseError::MissingParameter("e".to_string()))?,
+                )?;
+            } else if i == AKP_PUB && kty == AKP {
+                e.bytes(
+                    self.akp_pub
+                        .as_ref()
+                        .ok_or_else(|| CoseError::MissingParameter("pub".to_string()))?,
+                )?;
+            } else if i == AKP_PRIV && kty == AKP {
+                e.bytes(
+                    self.d
+                        .as_ref()
+                        .ok_or_else(|| CoseError::MissingParameter("priv".to_string()))?,
+                )?;
             } else if i == CRV_K {
                 if self.crv != None {
                     e.i32(
@@ -330,6 +405,17 @@ impl CoseKey {
         }
     }
 
+    /// Decode a cose-Key from the front of `bytes`, which may be followed by
+    /// other data.  Returns the key and the number of bytes it used.
+    pub fn decode_prefix(bytes: &[u8]) -> Result<(Self, usize), CoseError> {
+        let mut key = Self::new();
+        let mut d = Decoder::new(Config::default(), Cursor::new(bytes.to_vec()));
+        key.decode_key(&mut d)?;
+        let used = d.into_reader().position() as usize;
+        key.bytes = bytes[..used].to_vec();
+        Ok((key, used))
+    }
+
     pub(crate) fn decode_key(&mut self, d: &mut Decoder<Cursor<Vec<u8>>>) -> CoseResult {
         let mut label: i32;
         self.labels_found = Vec::new();
@@ -393,6 +479,18 @@ impl CoseKey {
             } else if label == BASE_IV {
                 self.base_iv = Some(d.bytes()?);
                 self.used.push(label);
+            } else if label == N && self.kty == Some(RSA) {
+                self.n = Some(d.bytes()?);
+                self.used.push(label);
+            } else if label == E && self.kty == Some(RSA) {
+                self.e = Some(d.bytes()?);
+                self.used.push(label);
+            } else if label == AKP_PUB && self.kty == Some(AKP) {
+                self.akp_pub = Some(d.bytes()?);
+                self.used.push(label);
+            } else if label == AKP_PRIV && self.kty == Some(AKP) {
+                self.d = Some(d.bytes()?);
+                self.used.push(label);
             } else if label == CRV_K {
                 if self
                     .kty
@@ -447,9 +545,31 @@ impl CoseKey {
         Ok(())
     }
 
+    /// The public key in the form [algs::verify] takes.  RSA keys are DER
+    /// encoded, as a SubjectPublicKeyInfo.  AKP keys are the encoded ML-DSA
+    /// public key.
     pub fn get_pub_key(&self, alg: i32) -> CoseResultWithRet<Vec<u8>> {
         let mut pub_key: Vec<u8>;
-        if algs::SIGNING_ALGS.contains(&alg) || algs::ECDH_ALGS.contains(&alg) {
+        if algs::RSA_SIGNING_ALGS.contains(&alg) {
+            pub_key = self.rsa_public_key_der()?;
+        } else if algs::ML_DSA_SIGNING_ALGS.contains(&alg) {
+            if self.kty != Some(AKP) {
+                return Err(CoseError::InvalidParameter("kty".to_string()));
+            }
+            pub_key = self
+                .akp_pub
+                .as_ref()
+                .ok_or_else(|| CoseError::MissingParameter("pub".to_string()))?
+                .to_vec();
+        } else if algs::SIGNING_ALGS.contains(&alg) || algs::ECDH_ALGS.contains(&alg) {
+            if algs::SIGNING_ALGS.contains(&alg) {
+                let crv = self
+                    .crv
+                    .ok_or_else(|| CoseError::MissingParameter("crv".to_string()))?;
+                if !signing_curves(alg).contains(&crv) {
+                    return Err(CoseError::InvalidParameter("crv".to_string()));
+                }
+            }
             let mut x = self
                 .x
                 .as_ref()
@@ -461,8 +581,12 @@ impl CoseKey {
             if algs::EDDSA == alg {
                 //DER prefixes
                 //302e020100300506032b657004220420 -> priv
-                //302a300506032b6570032100 -> pub
-                pub_key = vec![48, 42, 48, 5, 6, 3, 43, 101, 112, 3, 33, 0];
+                //302a300506032b6570032100 -> Ed25519 pub
+                //3043300506032b6571033a00 -> Ed448 pub
+                pub_key = match self.crv {
+                    Some(ED448) => vec![48, 67, 48, 5, 6, 3, 43, 101, 113, 3, 58, 0],
+                    _ => vec![48, 42, 48, 5, 6, 3, 43, 101, 112, 3, 33, 0],
+                };
                 pub_key.append(&mut x);
             } else if self.y == None {
                 pub_key = vec![3];
@@ -482,6 +606,36 @@ impl CoseKey {
         }
         Ok(pub_key)
     }
+
+    /// DER encoded SubjectPublicKeyInfo of an RSA key.
+    pub fn rsa_public_key_der(&self) -> CoseResultWithRet<Vec<u8>> {
+        if self.kty != Some(RSA) {
+            return Err(CoseError::InvalidParameter("kty".to_string()));
+        }
+        let n = self
+            .n
+            .as_ref()
+            .ok_or_else(|| CoseError::MissingParameter("n".to_string()))?;
+        let e = self
+            .e
+            .as_ref()
+            .ok_or_else(|| CoseError::MissingParameter("e".to_string()))?;
+        let rsa = Rsa::from_public_components(BigNum::from_slice(n)?, BigNum::from_slice(e)?)?;
+        Ok(PKey::from_rsa(rsa)?.public_key_to_der()?)
+    }
+}
+
+/// The curves a key may be on to sign with `alg`.  Each ECDSA algorithm fixes
+/// its curve, while EdDSA is used with either Edwards curve.
+fn signing_curves(alg: i32) -> &'static [i32] {
+    match alg {
+        algs::ES256 => &[P_256],
+        algs::ES384 => &[P_384],
+        algs::ES512 => &[P_521],
+        algs::ES256K => &[SECP256K1],
+        algs::EDDSA => &[ED25519, ED448],
+        _ => &[],
+    }
 }
 
 /// cose-keySet structure.
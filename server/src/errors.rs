@@ -1,4 +1,5 @@
 use crate::cose::errors::CoseError;
+use crate::webauthn::model::PolicyViolation;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,6 +8,8 @@ pub enum Error {
     ServiceError(String),
     #[error("mongodb error: {0}")]
     DatabaseError(#[from] mongodb::error::Error),
+    #[error("could not serialize to bson: {0}")]
+    BsonSerializeError(#[from] mongodb::bson::ser::Error),
     #[error("could not access field in document: {0}")]
     MongoDataError(#[from] mongodb::bson::document::ValueAccessError),
     #[error("Not found")]
@@ -61,8 +64,16 @@ pub enum Error {
     BadChallenge,
     #[error("Origin does not match")]
     BadOrigin,
+    #[error("Cross-origin ceremonies are not allowed")]
+    CrossOriginNotAllowed,
+    #[error("Top origin is not allowed")]
+    BadTopOrigin,
     #[error("Bad credential counter")]
     BadSignCounter,
+    #[error("userPresent flag not set")]
+    UserNotPresent,
+    #[error("userVerified flag not set")]
+    UserNotVerified,
     #[error("Attribute not found: {0}")]
     AttributeNotFound(String),
     #[error("Bad url: {0}")]
@@ -85,20 +96,59 @@ pub enum Error {
     ChallengeNotFound,
     #[error("Challenge was already used")]
     ChallengeUsed,
+    #[error("Challenge has expired")]
+    ChallengeExpired,
+    #[error("Challenge was not issued for this {0}")]
+    ChallengeMismatch(String),
     #[error("WebauthnPolicyBuilder cannont have {0} as None")]
     EmptyWebauthnPolicy(String),
+    #[error("Invalid WebAuthn policy: {0}")]
+    InvalidWebauthnPolicy(String),
+    #[error("Invalid WebAuthn policy, with {} violations", .0.len())]
+    PolicyViolations(Vec<PolicyViolation>),
+    #[error("Unknown tenant: {0}")]
+    UnknownTenant(String),
+    #[error("Unknown user: {0}")]
+    UnknownUser(String),
+    #[error("Invalid tenant: {0}")]
+    InvalidTenant(String),
+    #[error("Host conflict: {0}")]
+    HostConflict(String),
+    #[error("A valid bootstrap token is required to register {0}")]
+    BootstrapTokenRequired(String),
+    #[error("The config has changed since it was read")]
+    ConfigVersionConflict,
     #[error("Bad search doc for MDS")]
     BadMdsSearch,
+    #[error("PRF error: {0}")]
+    PrfError(String),
+    #[error("Large blob error: {0}")]
+    LargeBlobError(String),
+    #[error("Credential protection error: {0}")]
+    CredentialProtectionError(String),
 }
 
 use actix_web::{http::StatusCode, HttpResponse};
 
 impl actix_web::ResponseError for Error {
     fn error_response(&self) -> HttpResponse {
+        if let Error::PolicyViolations(violations) = self {
+            let body = serde_json::json!({ "message": self.to_string(), "violations": violations });
+            return HttpResponse::build(self.status_code()).json(body);
+        }
         HttpResponse::build(self.status_code()).json(format!(r#"{{"message": "{}"}}"#, self))
     }
 
     fn status_code(&self) -> StatusCode {
-        StatusCode::INTERNAL_SERVER_ERROR
+        match self {
+            Error::InvalidWebauthnPolicy(_)
+            | Error::PolicyViolations(_)
+            | Error::InvalidTenant(_) => StatusCode::BAD_REQUEST,
+            Error::UnknownTenant(_) | Error::UnknownUser(_) => StatusCode::NOT_FOUND,
+            Error::BootstrapTokenRequired(_) => StatusCode::FORBIDDEN,
+            Error::HostConflict(_) => StatusCode::CONFLICT,
+            Error::ConfigVersionConflict => StatusCode::PRECONDITION_FAILED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 }
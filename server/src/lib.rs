@@ -7,7 +7,7 @@ use actix_web::{
     Either, HttpResponse, Responder, Result,
 };
 
-pub use services::{DataServices, Session};
+pub use services::{DataServices, Session, TenantServices};
 pub mod api;
 pub mod config;
 pub mod cose;
@@ -30,6 +30,11 @@ async fn main() -> std::io::Result<()> {
             .wrap(middleware::Logger::default())
             .configure(webauthn::routes)
             .configure(api::routes)
+            .service(
+                web::scope("/t/{tenant}")
+                    .configure(webauthn::routes)
+                    .configure(api::routes),
+            )
             .default_service(web::to(server::default_handler))
     })
     .bind(("127.0.0.1", 3001))?
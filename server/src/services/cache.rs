@@ -5,14 +5,18 @@ use redis::{aio::ConnectionManager, AsyncCommands, Client, Value};
 use std::env;
 
 use super::SessionData;
-use crate::errors::Error;
+use crate::{config::DEFAULT_TENANT, errors::Error};
 
 const SESSIONS_KEY: &str = "sessions";
+const NONCES_KEY: &str = "challenge_nonces";
 
+/// Keys are prefixed with the tenant, except for the default tenant, so that
+/// a session from one tenant is not found by another.
 #[derive(Clone)]
 pub struct Cache {
     pub client: Client,
     pub connection_manager: ConnectionManager,
+    pub tenant: String,
 }
 
 impl Cache {
@@ -29,9 +33,27 @@ impl Cache {
         Ok(Self {
             client,
             connection_manager,
+            tenant: DEFAULT_TENANT.to_string(),
         })
     }
 
+    /// The same connection, with the keys of `tenant`
+    pub fn for_tenant(&self, tenant: &str) -> Self {
+        Self {
+            client: self.client.clone(),
+            connection_manager: self.connection_manager.clone(),
+            tenant: tenant.to_string(),
+        }
+    }
+
+    fn cache_key(&self, kind: &str, id: &str) -> String {
+        if self.tenant == DEFAULT_TENANT {
+            format!("{}:{}", kind, id)
+        } else {
+            format!("{}:{}:{}", self.tenant, kind, id)
+        }
+    }
+
     /// The URL format is redis://[<username>][:<password>@]<hostname>[:port][/<db>]
     pub fn connection() -> String {
         let conn = env::var("REDIS_URI").unwrap_or_else(|_| "redis://127.0.0.1".to_owned());
@@ -44,7 +66,7 @@ impl Cache {
         id: &Base64UrlSafeData,
         data: &SessionData,
     ) -> Result<(), Error> {
-        let cache_key = format!("{}:{}", SESSIONS_KEY, id).to_owned();
+        let cache_key = self.cache_key(SESSIONS_KEY, &id.to_string());
         let mut con = self.client.get_async_connection().await?;
         let data = serde_json::to_vec(data).map_err(Error::SerdeJsonError)?;
         con.set(&cache_key, data).await?;
@@ -52,11 +74,19 @@ impl Cache {
         Ok(())
     }
 
+    pub async fn delete_session(&self, id: &Base64UrlSafeData) -> Result<(), Error> {
+        let cache_key = self.cache_key(SESSIONS_KEY, &id.to_string());
+        let mut con = self.client.get_async_connection().await?;
+        let _: () = con.del(&cache_key).await?;
+
+        Ok(())
+    }
+
     pub async fn fetch_session(
         &self,
         id: &Base64UrlSafeData,
     ) -> Result<Option<SessionData>, Error> {
-        let cache_key = format!("{}:{}", SESSIONS_KEY, id).to_owned();
+        let cache_key = self.cache_key(SESSIONS_KEY, &id.to_string());
         let mut con = self.client.get_async_connection().await?;
         let cache_response = con.get(&cache_key).await?;
 
@@ -66,4 +96,23 @@ impl Cache {
             _ => Err(Error::GeneralError),
         }
     }
+
+    /// Record a stateless challenge nonce as used.  Returns false if it was
+    /// already recorded.  The entry expires after `ttl_ms`, by which time the
+    /// challenge itself has expired.
+    pub async fn consume_nonce(&self, nonce: &[u8], ttl_ms: i64) -> Result<bool, Error> {
+        let nonce = Base64UrlSafeData(nonce.to_vec()).to_string();
+        let cache_key = self.cache_key(NONCES_KEY, &nonce);
+        let mut con = self.client.get_async_connection().await?;
+        let result: Option<String> = redis::cmd("SET")
+            .arg(&cache_key)
+            .arg(1)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl_ms.max(1))
+            .query_async(&mut con)
+            .await?;
+
+        Ok(result.is_some())
+    }
 }
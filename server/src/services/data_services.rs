@@ -1,10 +1,20 @@
+use actix_web::HttpRequest;
 use base64urlsafedata::Base64UrlSafeData;
+use chrono::{Duration, Utc};
+use ring::constant_time;
 
-use super::{Cache, Challenge, SessionData, User, DB};
+use super::{
+    Cache, Challenge, ChallengeBinding, ConfigChange, PolicyDryRun, Role, SessionData,
+    StatelessChallenges, User, DB, TENANT_PATH_PARAM,
+};
 use crate::{
-    config::AppConfig,
+    config::{
+        is_valid_tenant, AdminConfig, AppConfig, AppConfigBuilder, ChallengeConfig, ChallengeMode,
+        DEFAULT_TENANT,
+    },
     errors::Error,
-    webauthn::model::{Credential, UserEntity, WebauthnPolicy, WebauthnPolicyBuilder},
+    utils::{json_diff, make_id},
+    webauthn::model::{Credential, UserEntity, WebauthnPolicyBuilder, PRF_SALT_LEN},
 };
 
 // Service wrapper for cache and database
@@ -18,6 +28,12 @@ pub struct DataServices {
     /// Represents the Redis cache client
     pub cache: Cache,
     pub db: DB,
+    /// Env driven settings for the admin API
+    pub admin: AdminConfig,
+    /// Env driven choice of stored or stateless challenges
+    pub challenges: ChallengeConfig,
+    /// The tenant that `db` and `cache` are scoped to
+    pub tenant: String,
 }
 
 impl DataServices {
@@ -27,33 +43,287 @@ impl DataServices {
     pub async fn create() -> Result<DataServices, Error> {
         let cache = Cache::create().await?;
         let db = DB::create().await?;
-        Ok(DataServices { cache, db })
+        let admin = AdminConfig::from_env();
+        let challenges = ChallengeConfig::from_env();
+        let services = DataServices {
+            cache,
+            db,
+            admin,
+            challenges,
+            tenant: DEFAULT_TENANT.to_string(),
+        };
+
+        // The bootstrap admin may have registered before the env was set.
+        if let Some(name) = services.admin.bootstrap_user.clone() {
+            services.bootstrap_admin(&name).await?;
+        }
+        Ok(services)
+    }
+
+    /// The same services, scoped to the data of `tenant`
+    pub fn for_tenant(&self, tenant: &str) -> DataServices {
+        DataServices {
+            cache: self.cache.for_tenant(tenant),
+            db: self.db.for_tenant(tenant),
+            admin: self.admin.clone(),
+            challenges: self.challenges.clone(),
+            tenant: tenant.to_string(),
+        }
+    }
+
+    /// The services of the tenant a request is for: the tenant named by a
+    /// `/t/{tenant}` path prefix, else the tenant that claims the request's
+    /// host, else the default tenant.
+    pub async fn for_request(&self, req: &HttpRequest) -> Result<DataServices, Error> {
+        if let Some(tenant) = req.match_info().get(TENANT_PATH_PARAM) {
+            let services = self.for_tenant(tenant);
+            if tenant != DEFAULT_TENANT && services.db.fetch_config().await?.is_none() {
+                return Err(Error::UnknownTenant(tenant.to_string()));
+            }
+            return Ok(services);
+        }
+
+        // Hosts may be configured with or without the port
+        let host = req.connection_info().host().to_string();
+        let hostname = host.split(':').next().unwrap_or_default();
+        if let Some(config) = self.db.fetch_config_by_host(&host).await? {
+            return Ok(self.for_tenant(&config.tenant));
+        }
+        if let Some(config) = self.db.fetch_config_by_host(hostname).await? {
+            return Ok(self.for_tenant(&config.tenant));
+        }
+        Ok(self.for_tenant(DEFAULT_TENANT))
     }
 
+    /// The config of the tenant.  The default tenant's config is created from
+    /// the env when it is first needed.
     pub async fn get_config(&self) -> Result<AppConfig, Error> {
         let result = self.db.fetch_config().await?;
         match result {
             Some(config) => Ok(config),
-            None => {
-                let config = AppConfig::default();
-                self.put_config(&config).await?;
-                Ok(config)
+            None if self.tenant == DEFAULT_TENANT => {
+                self.save_config(None, AppConfig::default(), None, None)
+                    .await
             }
+            None => Err(Error::UnknownTenant(self.tenant.clone())),
         }
     }
 
-    pub async fn put_config(&self, config: &AppConfig) -> Result<(), Error> {
-        self.db.put_config(config).await
+    /// Store `config` as the version after `previous`, and record the change
+    /// in the history.  Fails with
+    /// [ConfigVersionConflict](Error::ConfigVersionConflict) if the stored
+    /// config is no longer `previous`.
+    async fn save_config(
+        &self,
+        previous: Option<&AppConfig>,
+        mut config: AppConfig,
+        changed_by: Option<&str>,
+        rolled_back_to: Option<u64>,
+    ) -> Result<AppConfig, Error> {
+        let expected_version = previous.map_or(0, |previous| previous.version);
+        config.version = expected_version + 1;
+        self.db.put_config(&config, expected_version).await?;
+
+        let diff = match previous {
+            Some(previous) => {
+                let from = serde_json::to_value(previous).map_err(Error::SerdeJsonError)?;
+                let to = serde_json::to_value(&config).map_err(Error::SerdeJsonError)?;
+                json_diff(&from, &to)
+                    .into_iter()
+                    .filter(|change| change.path != "version")
+                    .collect()
+            }
+            None => vec![],
+        };
+        let change = ConfigChange {
+            tenant: config.tenant.clone(),
+            version: config.version,
+            changed_by: changed_by.map(str::to_string),
+            changed_at: Utc::now(),
+            rolled_back_to,
+            diff,
+            config: config.clone(),
+        };
+        self.db.add_config_change(&change).await?;
+        Ok(config)
     }
 
+    /// Patch the policy of the tenant.  With an `expected_version`, the patch
+    /// is refused if the config has another version.
     pub async fn patch_policy(
         &self,
         policy: WebauthnPolicyBuilder,
-    ) -> Result<WebauthnPolicy, Error> {
-        let mut config = self.get_config().await?;
+        changed_by: &str,
+        expected_version: Option<u64>,
+    ) -> Result<AppConfig, Error> {
+        let current = self.get_config().await?;
+        if expected_version.is_some_and(|version| version != current.version) {
+            return Err(Error::ConfigVersionConflict);
+        }
+        let mut config = current.clone();
         config.webauthn.update(policy)?;
-        self.put_config(&config).await?;
-        Ok(config.webauthn)
+        self.save_config(Some(&current), config, Some(changed_by), None)
+            .await
+    }
+
+    /// What patching the policy would change, without saving it.  An invalid
+    /// patch is reported rather than refused.
+    pub async fn dry_run_policy(
+        &self,
+        policy: WebauthnPolicyBuilder,
+        expected_version: Option<u64>,
+    ) -> Result<PolicyDryRun, Error> {
+        let current = self.get_config().await?;
+        if expected_version.is_some_and(|version| version != current.version) {
+            return Err(Error::ConfigVersionConflict);
+        }
+        let mut webauthn = current.webauthn.clone();
+        webauthn.apply(policy);
+
+        let from = serde_json::to_value(&current.webauthn).map_err(Error::SerdeJsonError)?;
+        let to = serde_json::to_value(&webauthn).map_err(Error::SerdeJsonError)?;
+        let violations = webauthn.violations();
+        Ok(PolicyDryRun {
+            version: current.version,
+            valid: violations.is_empty(),
+            violations,
+            diff: json_diff(&from, &to),
+            policy: webauthn,
+        })
+    }
+
+    /// The changes to the config of the tenant, newest first
+    pub async fn get_config_changes(&self) -> Result<Vec<ConfigChange>, Error> {
+        self.db.fetch_config_changes().await
+    }
+
+    /// Restore the config of an earlier version.  The restored config is
+    /// saved as a new version.
+    pub async fn rollback_config(
+        &self,
+        version: u64,
+        changed_by: &str,
+        expected_version: Option<u64>,
+    ) -> Result<AppConfig, Error> {
+        let current = self.get_config().await?;
+        if expected_version.is_some_and(|version| version != current.version) {
+            return Err(Error::ConfigVersionConflict);
+        }
+        let change = match self.db.fetch_config_change(version).await? {
+            Some(change) => change,
+            None => return Err(Error::NotFound),
+        };
+        // The hosts may have moved to another tenant since, and the rules
+        // for a valid policy may have changed
+        self.check_hosts(&current.tenant, &change.config.hosts)
+            .await?;
+        change.config.webauthn.validate()?;
+        self.save_config(
+            Some(&current),
+            change.config,
+            Some(changed_by),
+            Some(version),
+        )
+        .await
+    }
+
+    /// The current config of every tenant
+    pub async fn get_tenants(&self) -> Result<Vec<AppConfig>, Error> {
+        let mut tenants: Vec<AppConfig> = Vec::new();
+        for config in self.db.fetch_configs().await? {
+            match tenants
+                .iter_mut()
+                .find(|tenant| tenant.tenant == config.tenant)
+            {
+                Some(tenant) if tenant.version < config.version => *tenant = config,
+                Some(_) => {}
+                None => tenants.push(config),
+            }
+        }
+        Ok(tenants)
+    }
+
+    /// Create a tenant from the default config, or change an existing one.
+    /// A host can only belong to one tenant.  `admin` names a user of the
+    /// tenant, with a registered credential, that is granted [Role::Admin].
+    pub async fn put_tenant(
+        &self,
+        tenant: &str,
+        builder: AppConfigBuilder,
+        admin: Option<&str>,
+        changed_by: &str,
+    ) -> Result<AppConfig, Error> {
+        if !is_valid_tenant(tenant) {
+            return Err(Error::InvalidTenant(tenant.to_string()));
+        }
+        let services = self.for_tenant(tenant);
+        let previous = services.db.fetch_config().await?;
+        let admin = match admin {
+            Some(name) => match services.db.fetch_user_by_name(name).await? {
+                Some(user) if user.credentials.as_ref().is_some_and(|ids| !ids.is_empty()) => {
+                    Some(user)
+                }
+                _ => return Err(Error::UnknownUser(name.to_string())),
+            },
+            None => None,
+        };
+        let mut config = match &previous {
+            Some(config) => config.clone(),
+            None => AppConfig {
+                tenant: tenant.to_string(),
+                ..AppConfig::default()
+            },
+        };
+
+        if let Some(hosts) = builder.hosts {
+            self.check_hosts(tenant, &hosts).await?;
+            config.hosts = hosts;
+        }
+        if let Some(webauthn) = builder.webauthn {
+            config.webauthn.update(webauthn)?;
+        }
+
+        services.db.create_indexes().await?;
+        let config = services
+            .save_config(previous.as_ref(), config, Some(changed_by), None)
+            .await?;
+
+        if let Some(user) = admin.filter(|user| !user.is_admin()) {
+            let mut roles = user.roles;
+            roles.push(Role::Admin);
+            services.db.put_user_roles(&user.name, &roles).await?;
+            log::info!("Granted admin role of {} to {}", tenant, user.name);
+        }
+        Ok(config)
+    }
+
+    /// A host can only belong to one tenant
+    async fn check_hosts(&self, tenant: &str, hosts: &[String]) -> Result<(), Error> {
+        for host in hosts {
+            if let Some(other) = self.db.fetch_config_by_host(host).await? {
+                if other.tenant != tenant {
+                    return Err(Error::HostConflict(format!(
+                        "{} is a host of {}",
+                        host, other.tenant
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete a tenant, with its users, credentials and challenges.  The
+    /// default tenant cannot be deleted.
+    pub async fn delete_tenant(&self, tenant: &str) -> Result<(), Error> {
+        if tenant == DEFAULT_TENANT {
+            return Err(Error::InvalidTenant(
+                "The default tenant cannot be deleted".to_string(),
+            ));
+        }
+        if self.for_tenant(tenant).db.fetch_config().await?.is_none() {
+            return Err(Error::UnknownTenant(tenant.to_string()));
+        }
+        self.db.delete_tenant(tenant).await
     }
 
     pub async fn get_users(&self) -> Result<Vec<User>, Error> {
@@ -74,6 +344,99 @@ impl DataServices {
         }
     }
 
+    /// Fetch the stored [User], including the roles granted to it.
+    pub async fn get_stored_user(&self, name: &str) -> Result<Option<User>, Error> {
+        self.db.fetch_user_by_name(name).await
+    }
+
+    /// Replace the roles granted to a user.
+    pub async fn put_user_roles(&self, name: &str, roles: &[Role]) -> Result<(), Error> {
+        self.db.put_user_roles(name, roles).await
+    }
+
+    /// Grant [Role::Admin] to `name` if it is the configured bootstrap user and
+    /// no admin exists yet.  Only the default tenant has a bootstrap user, the
+    /// admins of other tenants are named with [put_tenant](Self::put_tenant).  The user must have completed registration, so that
+    /// a half finished registration cannot claim the role.  Returns true if the
+    /// role was granted.
+    pub async fn bootstrap_admin(&self, name: &str) -> Result<bool, Error> {
+        if self.tenant != DEFAULT_TENANT || self.admin.bootstrap_user.as_deref() != Some(name) {
+            return Ok(false);
+        }
+        if self.db.count_users_with_role(Role::Admin).await? > 0 {
+            return Ok(false);
+        }
+        let user = match self.db.fetch_user_by_name(name).await? {
+            Some(user) => user,
+            None => return Ok(false),
+        };
+        if user.credentials.as_ref().is_none_or(|ids| ids.is_empty()) {
+            return Ok(false);
+        }
+
+        let mut roles = user.roles;
+        roles.push(Role::Admin);
+        self.db.put_user_roles(name, &roles).await?;
+        log::info!("Granted admin role to bootstrap user: {}", name);
+        Ok(true)
+    }
+
+    /// Check a registration of `name` against the bootstrap user.  While no
+    /// admin exists, the bootstrap user can only be registered with
+    /// `token` matching `ADMIN_BOOTSTRAP_TOKEN`, so that the role does not go
+    /// to whoever registers the name first.  Returns true if this registration
+    /// is the bootstrap claim.
+    pub async fn check_bootstrap_claim(
+        &self,
+        name: &str,
+        token: Option<&str>,
+    ) -> Result<bool, Error> {
+        if self.tenant != DEFAULT_TENANT || self.admin.bootstrap_user.as_deref() != Some(name) {
+            return Ok(false);
+        }
+        if self.db.count_users_with_role(Role::Admin).await? > 0 {
+            return Ok(false);
+        }
+        match (self.admin.bootstrap_token.as_deref(), token) {
+            (Some(expected), Some(token))
+                if constant_time::verify_slices_are_equal(
+                    expected.as_bytes(),
+                    token.as_bytes(),
+                )
+                .is_ok() =>
+            {
+                Ok(true)
+            }
+            _ => Err(Error::BootstrapTokenRequired(name.to_string())),
+        }
+    }
+
+    /// The user that registers a credential as `request.name`.  A free name is
+    /// saved as a new user.  A registered user can only add a credential from
+    /// a session signed in as that user, `signed_in_as`.  Returns None if the
+    /// name is taken.
+    pub async fn registering_user(
+        &self,
+        request: &UserEntity,
+        signed_in_as: Option<&str>,
+    ) -> Result<Option<UserEntity>, Error> {
+        if let Some(user) = self.get_user(&request.name).await? {
+            if signed_in_as == Some(user.name.as_str()) {
+                return Ok(Some(user));
+            }
+            return Ok(None);
+        }
+
+        // Generate the user handle here, rather than trusting one from the
+        // request, so that the challenge can be bound to it.
+        let user = UserEntity::builder()
+            .with_name(&request.name)
+            .with_display_name(&request.display_name)
+            .build()?;
+        self.add_user(&user).await?;
+        Ok(Some(user))
+    }
+
     /// Add a user
     pub async fn add_user(&self, user: &UserEntity) -> Result<(), Error> {
         self.db.add_user(user).await?;
@@ -106,6 +469,37 @@ impl DataServices {
         Ok(())
     }
 
+    /// Start rotating the PRF salt of a credential.  Until the rotation is
+    /// committed, assertions evaluate the PRF with both salts, so that the
+    /// client can re-encrypt its data.  Starting again replaces the new salt.
+    pub async fn rotate_prf_salt(&self, cred: &Credential) -> Result<Credential, Error> {
+        if !cred.prf_enabled || cred.prf_salt.is_none() {
+            return Err(Error::PrfError("PRF is not enabled".to_string()));
+        }
+        let mut cred = cred.clone();
+        cred.prf_next_salt = Some(Base64UrlSafeData(make_id(PRF_SALT_LEN)?));
+        self.db
+            .put_credential_prf_salts(&cred.id, &cred.prf_salt, &cred.prf_next_salt)
+            .await?;
+        Ok(cred)
+    }
+
+    /// Replace the PRF salt of a credential with the new salt from
+    /// [rotate_prf_salt](Self::rotate_prf_salt).
+    pub async fn commit_prf_salt(&self, cred: &Credential) -> Result<Credential, Error> {
+        if cred.prf_next_salt.is_none() {
+            return Err(Error::PrfError(
+                "No PRF salt rotation is pending".to_string(),
+            ));
+        }
+        let mut cred = cred.clone();
+        cred.prf_salt = cred.prf_next_salt.take();
+        self.db
+            .put_credential_prf_salts(&cred.id, &cred.prf_salt, &cred.prf_next_salt)
+            .await?;
+        Ok(cred)
+    }
+
     /// TODO: Reverse fetching - we need to be able to fetch a user from a credential,
     /// since a user may have more than 1.
     pub async fn get_user_credential(&self, name: &str) -> Result<Option<Credential>, Error> {
@@ -125,6 +519,37 @@ impl DataServices {
         self.get_credential(&cred_ids[0]).await
     }
 
+    /// Fetch every [Credential] registered to a user, in registration order.
+    pub async fn get_user_credentials(&self, name: &str) -> Result<Vec<Credential>, Error> {
+        let user = match self.db.fetch_user_by_name(name).await? {
+            Some(user) => user,
+            None => return Err(Error::NotFound),
+        };
+        let mut credentials = vec![];
+        for id in user.credentials.unwrap_or_default() {
+            if let Some(cred) = self.get_credential(&id).await? {
+                credentials.push(cred);
+            }
+        }
+        Ok(credentials)
+    }
+
+    /// True if the user has a single credential, and it is not backed up.
+    /// Losing that authenticator would lock the user out.
+    pub async fn only_credential_not_backed_up(&self, name: &str) -> Result<bool, Error> {
+        let credentials = self.get_user_credentials(name).await?;
+        Ok(credentials.len() == 1 && !credentials[0].backup_state)
+    }
+
+    /// True if the user has a discoverable credential, so that they can sign
+    /// in without entering their name.
+    pub async fn can_sign_in_without_username(&self, name: &str) -> Result<bool, Error> {
+        let credentials = self.get_user_credentials(name).await?;
+        Ok(credentials
+            .iter()
+            .any(|cred| cred.discoverable == Some(true)))
+    }
+
     pub async fn put_session(
         &self,
         id: &Base64UrlSafeData,
@@ -133,13 +558,37 @@ impl DataServices {
         self.cache.put_session(id, data).await
     }
 
+    pub async fn delete_session(&self, id: &Base64UrlSafeData) -> Result<(), Error> {
+        self.cache.delete_session(id).await
+    }
+
     pub async fn get_session(&self, id: &Base64UrlSafeData) -> Result<Option<SessionData>, Error> {
         self.cache.fetch_session(id).await
     }
 
-    /// Generate a new challenge and store it.
-    pub async fn create_new_challenge(&self) -> Result<Challenge, Error> {
-        let challenge = Challenge::new();
+    /// The signer for stateless challenges, if they are enabled.
+    fn stateless_challenges(&self) -> Option<StatelessChallenges> {
+        match (self.challenges.mode, &self.challenges.key) {
+            (ChallengeMode::Stateless, Some(key)) => Some(StatelessChallenges::new(key)),
+            _ => None,
+        }
+    }
+
+    /// Generate a new challenge and store it.  The challenge is refused once
+    /// `ttl` has passed, or when used with a different binding.
+    ///
+    /// With stateless challenges nothing is stored.  The value is a signed
+    /// token carrying the binding and expiry.
+    pub async fn create_new_challenge(
+        &self,
+        binding: ChallengeBinding,
+        ttl: Duration,
+    ) -> Result<Challenge, Error> {
+        if let Some(stateless) = self.stateless_challenges() {
+            return stateless.issue(binding, ttl);
+        }
+
+        let challenge = Challenge::new(binding, ttl);
         if self.db.check_challenge(&challenge.value).await? {
             // The challenge already exists
             return Err(Error::ChallengeExists);
@@ -148,36 +597,94 @@ impl DataServices {
         Ok(challenge)
     }
 
-    /// Create a challenge from a value, and store it.
-    pub async fn create_challenge(&self, value: &Base64UrlSafeData) -> Result<(), Error> {
+    /// Create a challenge from a value, and store it.  Not supported with
+    /// stateless challenges, since the server must choose the value.
+    pub async fn create_challenge(
+        &self,
+        value: &Base64UrlSafeData,
+        binding: ChallengeBinding,
+        ttl: Duration,
+    ) -> Result<(), Error> {
+        if self.stateless_challenges().is_some() {
+            return Err(Error::ServiceError(
+                "Challenge values cannot be chosen with stateless challenges".to_string(),
+            ));
+        }
         if self.db.check_challenge(value).await? {
             // The challenge already exists
             return Err(Error::ChallengeExists);
         }
 
-        let challenge = Challenge::from(value);
+        let challenge = Challenge::from_value(value, binding, ttl);
         self.db.add_challenge(&challenge).await?;
         Ok(())
     }
 
     /// Fulfill the "use once" strategy.  Mark a stored challenge as used.
-    pub async fn use_challenge(&self, value: &Base64UrlSafeData) -> Result<(), Error> {
-        // See if the challenge exists.  Throw not found otherwise
-        let result = self.db.fetch_challenge(value).await?;
-        if result.is_none() {
-            return Err(Error::ChallengeNotFound);
+    /// `binding` describes the response, and must match the binding the
+    /// challenge was issued with.
+    ///
+    /// The challenge is marked used in a single conditional update, so that of
+    /// several concurrent responses with the same challenge only one succeeds.
+    /// Only a live challenge with the same binding is used, so a stale or
+    /// misdirected response leaves the challenge to its owner.
+    pub async fn use_challenge(
+        &self,
+        value: &Base64UrlSafeData,
+        binding: &ChallengeBinding,
+    ) -> Result<(), Error> {
+        if let Some(stateless) = self.stateless_challenges() {
+            return self
+                .use_stateless_challenge(&stateless, value, binding)
+                .await;
         }
-        let challenge = result.unwrap();
 
-        // See if the challenge is already used.  Throw already used if so
+        if self.db.consume_challenge(value, binding).await?.is_some() {
+            return Ok(());
+        }
+
+        // Tell why the challenge was refused
+        let challenge = match self.db.fetch_challenge(value).await? {
+            Some(challenge) => challenge,
+            None => return Err(Error::ChallengeNotFound),
+        };
         if challenge.used {
             return Err(Error::ChallengeUsed);
         }
+        if challenge.is_expired() {
+            return Err(Error::ChallengeExpired);
+        }
+        if let Some(attribute) = challenge.binding.mismatch(binding) {
+            return Err(Error::ChallengeMismatch(attribute.to_string()));
+        }
+        // Used by a concurrent response since
+        Err(Error::ChallengeUsed)
+    }
 
-        // Update the challenge, and return OK
-        let new = challenge.mark_used();
-        self.db.update_challenge(&new).await?;
+    /// Verify a stateless challenge token, then record its nonce so that the
+    /// token is accepted only once.  The nonce is kept until the token expires.
+    async fn use_stateless_challenge(
+        &self,
+        stateless: &StatelessChallenges,
+        value: &Base64UrlSafeData,
+        binding: &ChallengeBinding,
+    ) -> Result<(), Error> {
+        let token = stateless.verify(value, &binding.rp_id)?;
+        if token.is_expired() {
+            return Err(Error::ChallengeExpired);
+        }
+        if let Some(attribute) = token.binding.mismatch(binding) {
+            return Err(Error::ChallengeMismatch(attribute.to_string()));
+        }
 
+        let ttl = token.expires_at - Utc::now();
+        if !self
+            .cache
+            .consume_nonce(&token.nonce, ttl.num_milliseconds())
+            .await?
+        {
+            return Err(Error::ChallengeUsed);
+        }
         Ok(())
     }
 
@@ -196,3 +703,324 @@ impl DataServices {
         self.db.get_mds(search).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::Ceremony;
+    use futures::future::join_all;
+
+    /// Run with `docker compose up -d` and `cargo test -- --ignored`
+    #[actix_web::test]
+    #[ignore = "requires MongoDB and Redis, see docker-compose.yaml"]
+    async fn test_use_challenge_concurrently() -> Result<(), Error> {
+        let service = DataServices::create().await?;
+        let binding = ChallengeBinding {
+            ceremony: Ceremony::Get,
+            user_handle: None,
+            rp_id: "localhost".to_string(),
+            session_id: Base64UrlSafeData(vec![1, 2, 3]),
+        };
+        let challenge = service
+            .create_new_challenge(binding.clone(), Duration::minutes(1))
+            .await?;
+
+        let responses = (0..16).map(|_| service.use_challenge(&challenge.value, &binding));
+        let results = join_all(responses).await;
+
+        let succeeded = results.iter().filter(|r| r.is_ok()).count();
+        let refused = results
+            .iter()
+            .filter(|r| matches!(r, Err(Error::ChallengeUsed)))
+            .count();
+        assert_eq!(succeeded, 1);
+        assert_eq!(refused, results.len() - 1);
+
+        service.db.delete_challenge(&challenge.value).await?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires MongoDB and Redis, see docker-compose.yaml"]
+    async fn test_misdirected_response_leaves_challenge() -> Result<(), Error> {
+        let service = DataServices::create().await?;
+        let binding = ChallengeBinding {
+            ceremony: Ceremony::Get,
+            user_handle: None,
+            rp_id: "localhost".to_string(),
+            session_id: Base64UrlSafeData(vec![1, 2, 3]),
+        };
+        let challenge = service
+            .create_new_challenge(binding.clone(), Duration::minutes(1))
+            .await?;
+
+        let other = ChallengeBinding {
+            session_id: Base64UrlSafeData(vec![4, 5, 6]),
+            ..binding.clone()
+        };
+        let result = service.use_challenge(&challenge.value, &other).await;
+        assert!(matches!(result, Err(Error::ChallengeMismatch(_))));
+        let create = ChallengeBinding {
+            ceremony: Ceremony::Create,
+            ..binding.clone()
+        };
+        let result = service.use_challenge(&challenge.value, &create).await;
+        assert!(matches!(result, Err(Error::ChallengeMismatch(_))));
+
+        // The owner can still use it, once
+        service.use_challenge(&challenge.value, &binding).await?;
+        let result = service.use_challenge(&challenge.value, &binding).await;
+        assert!(matches!(result, Err(Error::ChallengeUsed)));
+
+        service.db.delete_challenge(&challenge.value).await?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires MongoDB and Redis, see docker-compose.yaml"]
+    async fn test_registering_user() -> Result<(), Error> {
+        let service = DataServices::create().await?;
+        let builder = AppConfigBuilder {
+            hosts: None,
+            webauthn: None,
+        };
+        service
+            .put_tenant("test-register", builder, None, "admin")
+            .await?;
+        let tenant = service.for_tenant("test-register");
+        let request = UserEntity::builder()
+            .with_name("register-test-user")
+            .with_display_name(&Some("Register Test".to_string()))
+            .build()?;
+
+        // A free name is a new user, with a handle of its own
+        let user = tenant.registering_user(&request, None).await?;
+        let user = user.expect("a new user");
+        assert_ne!(user.id, request.id);
+        assert!(tenant.check_user(&user.name).await?);
+
+        // The name is taken, unless the user is signed in
+        assert_eq!(tenant.registering_user(&request, None).await?, None);
+        let other = tenant.registering_user(&request, Some("other")).await?;
+        assert_eq!(other, None);
+        let again = tenant.registering_user(&request, Some(&user.name)).await?;
+        assert_eq!(again.map(|again| again.id), Some(user.id));
+
+        service.delete_tenant("test-register").await?;
+        Ok(())
+    }
+
+    /// Expects a database without admins
+    #[actix_web::test]
+    #[ignore = "requires MongoDB and Redis, see docker-compose.yaml"]
+    async fn test_bootstrap_claim() -> Result<(), Error> {
+        let mut service = DataServices::create().await?;
+        service.admin = AdminConfig {
+            bootstrap_user: Some("bootstrap-test-user".to_string()),
+            bootstrap_token: Some("secret".to_string()),
+            max_auth_age: None,
+        };
+        let request = UserEntity::builder()
+            .with_name("bootstrap-test-user")
+            .with_display_name(&Some("Bootstrap Test".to_string()))
+            .build()?;
+
+        // Other names are not a claim
+        assert!(!service.check_bootstrap_claim("other", None).await?);
+
+        // The bootstrap user needs the token
+        let result = service.check_bootstrap_claim(&request.name, None).await;
+        assert!(matches!(result, Err(Error::BootstrapTokenRequired(_))));
+        let result = service
+            .check_bootstrap_claim(&request.name, Some("guess"))
+            .await;
+        assert!(matches!(result, Err(Error::BootstrapTokenRequired(_))));
+
+        // The token holder registers the name
+        assert!(
+            service
+                .check_bootstrap_claim(&request.name, Some("secret"))
+                .await?
+        );
+        let user = service.registering_user(&request, None).await?;
+        assert!(user.is_some());
+
+        // A second registrant can neither claim nor take the name
+        let result = service.check_bootstrap_claim(&request.name, None).await;
+        assert!(matches!(result, Err(Error::BootstrapTokenRequired(_))));
+        assert_eq!(service.registering_user(&request, None).await?, None);
+
+        service.db.delete_user(&request.name).await?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires MongoDB and Redis, see docker-compose.yaml"]
+    async fn test_tenant_admin() -> Result<(), Error> {
+        let mut service = DataServices::create().await?;
+        service.admin = AdminConfig {
+            bootstrap_user: Some("tenant-admin-user".to_string()),
+            bootstrap_token: Some("secret".to_string()),
+            max_auth_age: None,
+        };
+        let builder = AppConfigBuilder {
+            hosts: None,
+            webauthn: None,
+        };
+        service
+            .put_tenant("test-admin", builder, None, "admin")
+            .await?;
+
+        // The bootstrap user of the default tenant is just a name in a tenant
+        let tenant = service.for_tenant("test-admin");
+        let claim = tenant
+            .check_bootstrap_claim("tenant-admin-user", None)
+            .await?;
+        assert!(!claim);
+        let user = UserEntity::builder()
+            .with_name("tenant-admin-user")
+            .with_display_name(&Some("Tenant Admin".to_string()))
+            .build()?;
+        tenant.add_user(&user).await?;
+        assert!(!tenant.bootstrap_admin(&user.name).await?);
+
+        // The admin is named explicitly, and must have registered
+        let builder = AppConfigBuilder {
+            hosts: None,
+            webauthn: None,
+        };
+        let result = service
+            .put_tenant("test-admin", builder, Some(&user.name), "admin")
+            .await;
+        assert!(matches!(result, Err(Error::UnknownUser(_))));
+        assert_eq!(tenant.db.count_users_with_role(Role::Admin).await?, 0);
+
+        service.delete_tenant("test-admin").await?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires MongoDB and Redis, see docker-compose.yaml"]
+    async fn test_tenants_are_isolated() -> Result<(), Error> {
+        let service = DataServices::create().await?;
+        let builder = AppConfigBuilder {
+            hosts: Some(vec!["brand.localhost".to_string()]),
+            webauthn: None,
+        };
+        service
+            .put_tenant("test-brand", builder, None, "admin")
+            .await?;
+
+        let brand = service.for_tenant("test-brand");
+        let user = UserEntity::builder()
+            .with_name("tenant-test-user")
+            .with_display_name(&Some("Tenant Test".to_string()))
+            .build()?;
+        brand.add_user(&user).await?;
+        assert!(brand.check_user(&user.name).await?);
+        assert!(!service.check_user(&user.name).await?);
+
+        // A host belongs to one tenant
+        let builder = AppConfigBuilder {
+            hosts: Some(vec!["brand.localhost".to_string()]),
+            webauthn: None,
+        };
+        let result = service
+            .put_tenant("test-other", builder, None, "admin")
+            .await;
+        assert!(matches!(result, Err(Error::HostConflict(_))));
+
+        service.delete_tenant("test-brand").await?;
+        let result = service.for_tenant("test-brand").get_config().await;
+        assert!(matches!(result, Err(Error::UnknownTenant(_))));
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires MongoDB and Redis, see docker-compose.yaml"]
+    async fn test_config_versions() -> Result<(), Error> {
+        let service = DataServices::create().await?;
+        let builder = AppConfigBuilder {
+            hosts: None,
+            webauthn: None,
+        };
+        let created = service
+            .put_tenant("test-versions", builder, None, "admin")
+            .await?;
+        let tenant = service.for_tenant("test-versions");
+
+        let patch = r#"{ "authentication": { "timeout": 1234 } }"#;
+        let policy: WebauthnPolicyBuilder =
+            serde_json::from_str(patch).map_err(Error::SerdeJsonError)?;
+        let patched = tenant
+            .patch_policy(policy.clone(), "admin", Some(created.version))
+            .await?;
+        assert_eq!(patched.version, created.version + 1);
+        assert_eq!(patched.webauthn.authentication.timeout, 1234);
+
+        // A patch against the old version is refused
+        let result = tenant
+            .patch_policy(policy, "admin", Some(created.version))
+            .await;
+        assert!(matches!(result, Err(Error::ConfigVersionConflict)));
+
+        let restored = tenant
+            .rollback_config(created.version, "admin", None)
+            .await?;
+        assert_eq!(restored.version, patched.version + 1);
+        assert_eq!(
+            restored.webauthn.authentication.timeout,
+            created.webauthn.authentication.timeout
+        );
+
+        let changes = tenant.get_config_changes().await?;
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].rolled_back_to, Some(created.version));
+        assert_eq!(changes[1].changed_by.as_deref(), Some("admin"));
+        assert!(changes[1]
+            .diff
+            .iter()
+            .any(|change| change.path == "webauthn.authentication.timeout"));
+
+        service.delete_tenant("test-versions").await?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires MongoDB and Redis, see docker-compose.yaml"]
+    async fn test_rollback_checks_hosts() -> Result<(), Error> {
+        let service = DataServices::create().await?;
+        let builder = AppConfigBuilder {
+            hosts: Some(vec!["rollback.localhost".to_string()]),
+            webauthn: None,
+        };
+        let created = service
+            .put_tenant("test-rollback", builder, None, "admin")
+            .await?;
+        let builder = AppConfigBuilder {
+            hosts: Some(vec![]),
+            webauthn: None,
+        };
+        service
+            .put_tenant("test-rollback", builder, None, "admin")
+            .await?;
+        let builder = AppConfigBuilder {
+            hosts: Some(vec!["rollback.localhost".to_string()]),
+            webauthn: None,
+        };
+        service
+            .put_tenant("test-rollback-other", builder, None, "admin")
+            .await?;
+
+        // The host now belongs to the other tenant
+        let result = service
+            .for_tenant("test-rollback")
+            .rollback_config(created.version, "admin", None)
+            .await;
+        assert!(matches!(result, Err(Error::HostConflict(_))));
+
+        service.delete_tenant("test-rollback").await?;
+        service.delete_tenant("test-rollback-other").await?;
+        Ok(())
+    }
+}
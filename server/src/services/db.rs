@@ -3,14 +3,20 @@
 use base64urlsafedata::Base64UrlSafeData;
 use futures::stream::TryStreamExt;
 use mongodb::{
-    bson::doc, bson::Document, options::ClientOptions, results::InsertOneResult, Client,
-    Collection, Database,
+    bson::doc,
+    bson::to_bson,
+    bson::DateTime,
+    bson::Document,
+    error::{ErrorKind, WriteFailure},
+    options::{ClientOptions, FindOptions, IndexOptions, ReplaceOptions},
+    results::InsertOneResult,
+    Client, Collection, Database, IndexModel,
 };
-use std::{collections::HashMap, env};
+use std::{collections::HashMap, env, time::Duration};
 
-use super::{Challenge, User};
+use super::{Challenge, ChallengeBinding, ConfigChange, Role, User};
 use crate::{
-    config::AppConfig,
+    config::{AppConfig, DEFAULT_TENANT},
     errors::Error,
     webauthn::model::{Credential, UserEntity},
 };
@@ -18,13 +24,19 @@ use crate::{
 static CRED_COLLECTION: &str = "credentials";
 static USER_COLLECTION: &str = "users";
 static APP_CONFIG_COLLECTION: &str = "appconfig";
+static APP_CONFIG_HISTORY_COLLECTION: &str = "appconfig_history";
 static WEBAUTHN_CHALLENGE_COLLECTION: &str = "webauthn_challenge";
 static MDS_COLLECTION: &str = "mds";
 
+/// Users, credentials and challenges are kept in collections of their own for
+/// each tenant, named `{tenant}.users` and so on.  The default tenant keeps
+/// the unprefixed collections, so data stored before tenants is still found.
+/// The app config, its history and the MDS collections are shared.
 #[derive(Clone, Debug)]
 pub struct DB {
     pub client: Client,
     pub database: Database,
+    pub tenant: String,
 }
 
 impl DB {
@@ -45,24 +57,69 @@ impl DB {
         let client = Client::with_options(client_options)
             .map_err(|_| Error::ServiceError("Failed to create MongoDB client".to_string()))?;
         let database = client.database(&database_name);
-        Ok(Self { client, database })
+        let db = Self {
+            client,
+            database,
+            tenant: DEFAULT_TENANT.to_string(),
+        };
+        db.create_indexes().await?;
+        Ok(db)
+    }
+
+    /// The same database, with the collections of `tenant`
+    pub fn for_tenant(&self, tenant: &str) -> Self {
+        Self {
+            client: self.client.clone(),
+            database: self.database.clone(),
+            tenant: tenant.to_string(),
+        }
+    }
+
+    fn tenant_collection(&self, name: &str) -> String {
+        if self.tenant == DEFAULT_TENANT {
+            name.to_string()
+        } else {
+            format!("{}.{}", self.tenant, name)
+        }
+    }
+
+    /// Mongo purges challenges once `expiresAt` has passed.  Creating an index
+    /// that already exists is a no-op.
+    pub async fn create_indexes(&self) -> Result<(), Error> {
+        let ttl = IndexModel::builder()
+            .keys(doc! {"expiresAt": 1})
+            .options(
+                IndexOptions::builder()
+                    .expire_after(Duration::from_secs(0))
+                    .build(),
+            )
+            .build();
+        self.challenges().create_index(ttl, None).await?;
+        Ok(())
     }
 
     fn users(&self) -> Collection<User> {
-        self.database.collection::<User>(USER_COLLECTION)
+        self.database
+            .collection::<User>(&self.tenant_collection(USER_COLLECTION))
     }
 
     fn credentials(&self) -> Collection<Credential> {
-        self.database.collection::<Credential>(CRED_COLLECTION)
+        self.database
+            .collection::<Credential>(&self.tenant_collection(CRED_COLLECTION))
     }
 
     fn app_config(&self) -> Collection<AppConfig> {
         self.database.collection::<AppConfig>(APP_CONFIG_COLLECTION)
     }
 
+    fn config_history(&self) -> Collection<ConfigChange> {
+        self.database
+            .collection::<ConfigChange>(APP_CONFIG_HISTORY_COLLECTION)
+    }
+
     fn challenges(&self) -> Collection<Challenge> {
         self.database
-            .collection::<Challenge>(WEBAUTHN_CHALLENGE_COLLECTION)
+            .collection::<Challenge>(&self.tenant_collection(WEBAUTHN_CHALLENGE_COLLECTION))
     }
 
     fn mds(&self) -> Collection<serde_json::Value> {
@@ -70,19 +127,112 @@ impl DB {
             .collection::<serde_json::Value>(MDS_COLLECTION)
     }
 
+    /// Configs stored before tenants have no tenant, and belong to the default
+    fn tenant_filter(tenant: &str) -> Document {
+        if tenant == DEFAULT_TENANT {
+            doc! {"$or": [{"tenant": tenant}, {"tenant": {"$exists": false}}]}
+        } else {
+            doc! {"tenant": tenant}
+        }
+    }
+
+    /// The current config has the tenant as its `_id`.  Before versions, every
+    /// change inserted another config, and the first one was used.
     pub async fn fetch_config(&self) -> Result<Option<AppConfig>, Error> {
-        let result = self.app_config().find_one(None, None).await?;
+        let result = self
+            .app_config()
+            .find_one(doc! {"_id": &self.tenant}, None)
+            .await?;
         if let Some(config) = result {
             return Ok(Some(config));
         }
-        Ok(None)
+        let filter = Self::tenant_filter(&self.tenant);
+        let result = self.app_config().find_one(filter, None).await?;
+        Ok(result)
+    }
+
+    /// The config of the tenant that claims `host`
+    pub async fn fetch_config_by_host(&self, host: &str) -> Result<Option<AppConfig>, Error> {
+        let result = self
+            .app_config()
+            .find_one(doc! {"hosts": host}, None)
+            .await?;
+        Ok(result)
+    }
+
+    /// The config of every tenant
+    pub async fn fetch_configs(&self) -> Result<Vec<AppConfig>, Error> {
+        let mut cursor = self.app_config().find(None, None).await?;
+        let mut configs: Vec<AppConfig> = Vec::new();
+        while let Some(config) = cursor.try_next().await? {
+            configs.push(config);
+        }
+        Ok(configs)
+    }
+
+    /// Delete the config and the collections of `tenant`
+    pub async fn delete_tenant(&self, tenant: &str) -> Result<(), Error> {
+        let db = self.for_tenant(tenant);
+        db.users().drop(None).await?;
+        db.credentials().drop(None).await?;
+        db.challenges().drop(None).await?;
+        self.app_config()
+            .delete_many(Self::tenant_filter(tenant), None)
+            .await?;
+        self.config_history()
+            .delete_many(doc! {"tenant": tenant}, None)
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the current config of the tenant, as long as it is still at
+    /// `expected_version`.  Otherwise fails with
+    /// [ConfigVersionConflict](Error::ConfigVersionConflict).  Configs stored
+    /// before versions are removed.
+    pub async fn put_config(&self, config: &AppConfig, expected_version: u64) -> Result<(), Error> {
+        let filter = doc! {"_id": &config.tenant, "version": expected_version as i64};
+        let options = ReplaceOptions::builder().upsert(true).build();
+        // With a newer version stored, the upsert collides with its `_id`
+        if let Err(err) = self.app_config().replace_one(filter, config, options).await {
+            return match *err.kind {
+                ErrorKind::Write(WriteFailure::WriteError(ref write)) if write.code == 11000 => {
+                    Err(Error::ConfigVersionConflict)
+                }
+                _ => Err(err.into()),
+            };
+        }
+
+        let tenant_filter = Self::tenant_filter(&config.tenant);
+        let legacy = doc! {"$and": [tenant_filter, {"_id": {"$ne": &config.tenant}}]};
+        self.app_config().delete_many(legacy, None).await?;
+        Ok(())
     }
 
-    pub async fn put_config(&self, config: &AppConfig) -> Result<(), Error> {
-        self.app_config().insert_one(config, None).await?;
+    pub async fn add_config_change(&self, change: &ConfigChange) -> Result<(), Error> {
+        self.config_history().insert_one(change, None).await?;
         Ok(())
     }
 
+    /// The changes to the tenant's config, newest first
+    pub async fn fetch_config_changes(&self) -> Result<Vec<ConfigChange>, Error> {
+        let options = FindOptions::builder().sort(doc! {"version": -1}).build();
+        let mut cursor = self
+            .config_history()
+            .find(doc! {"tenant": &self.tenant}, options)
+            .await?;
+        let mut changes: Vec<ConfigChange> = Vec::new();
+        while let Some(change) = cursor.try_next().await? {
+            changes.push(change);
+        }
+        Ok(changes)
+    }
+
+    pub async fn fetch_config_change(&self, version: u64) -> Result<Option<ConfigChange>, Error> {
+        let filter = doc! {"tenant": &self.tenant, "version": version as i64};
+        let result = self.config_history().find_one(filter, None).await?;
+        Ok(result)
+    }
+
     pub async fn check_user_by_name(&self, name: &str) -> Result<bool, Error> {
         let result = self
             .users()
@@ -118,6 +268,28 @@ impl DB {
         Ok(())
     }
 
+    /// Replace the set of roles granted to a user.
+    pub async fn put_user_roles(&self, name: &str, roles: &[Role]) -> Result<(), Error> {
+        let roles = to_bson(roles)?;
+        let result = self
+            .users()
+            .update_one(doc! {"name": name}, doc! {"$set": {"roles": roles}}, None)
+            .await?;
+        if result.matched_count == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    pub async fn count_users_with_role(&self, role: Role) -> Result<u64, Error> {
+        let role = to_bson(&role)?;
+        let result = self
+            .users()
+            .count_documents(doc! {"roles": role}, None)
+            .await?;
+        Ok(result)
+    }
+
     /// Caution!  Deleting a user, wihtout deleting the user's creds is bad!
     pub async fn delete_user(&self, name: &str) -> Result<(), Error> {
         self.users().delete_one(doc! {"name": name}, None).await?;
@@ -143,13 +315,40 @@ impl DB {
         self.credentials()
             .update_one(
                 doc! {"id": cred.id.to_string()},
-                doc! {"$set": {"counter": new_cred.counter, "last": new_cred.last.to_string()}},
+                doc! {"$set": {
+                    "counter": new_cred.counter,
+                    "last": new_cred.last.to_string(),
+                    "backup_state": new_cred.backup_state,
+                }},
                 None,
             )
             .await?;
         Ok(new_cred)
     }
 
+    /// Replace the PRF salts of a credential
+    pub async fn put_credential_prf_salts(
+        &self,
+        id: &Base64UrlSafeData,
+        salt: &Option<Base64UrlSafeData>,
+        next_salt: &Option<Base64UrlSafeData>,
+    ) -> Result<(), Error> {
+        let salt = to_bson(salt)?;
+        let next_salt = to_bson(next_salt)?;
+        let result = self
+            .credentials()
+            .update_one(
+                doc! {"id": id.to_string()},
+                doc! {"$set": {"prf_salt": salt, "prf_next_salt": next_salt}},
+                None,
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
     /// Caution!  Deleting a user, wihtout deleting the user's creds is bad!
     pub async fn delete_credential(&self, id: &Base64UrlSafeData) -> Result<(), Error> {
         self.credentials()
@@ -224,18 +423,34 @@ impl DB {
         Ok(false)
     }
 
-    /// the only update-able fields are the used and used_time fields
-    pub async fn update_challenge(&self, challenge: &Challenge) -> Result<Challenge, Error> {
-        let new = challenge.mark_used();
-
-        self.challenges()
-            .update_one(
-                doc! {"value": new.value.to_string()},
-                doc! {"$set": {"used": new.used, "used_time": new.used_time.unwrap().to_string()}},
+    /// Atomically mark an unused challenge as used.  Only a challenge that has
+    /// not expired and was issued with `binding` is used.  Returns the
+    /// challenge as it was before the update, or None if there is no such
+    /// challenge.
+    pub async fn consume_challenge(
+        &self,
+        value: &Base64UrlSafeData,
+        binding: &ChallengeBinding,
+    ) -> Result<Option<Challenge>, Error> {
+        let used_time = DateTime::now();
+        let filter = doc! {
+            "value": value.to_string(),
+            "used": false,
+            "expiresAt": {"$gt": used_time},
+            "binding.ceremony": to_bson(&binding.ceremony)?,
+            "binding.userHandle": to_bson(&binding.user_handle)?,
+            "binding.rpId": &binding.rp_id,
+            "binding.sessionId": to_bson(&binding.session_id)?,
+        };
+        let result = self
+            .challenges()
+            .find_one_and_update(
+                filter,
+                doc! {"$set": {"used": true, "usedTime": used_time}},
                 None,
             )
             .await?;
-        Ok(new)
+        Ok(result)
     }
 
     pub async fn delete_challenge(&self, value: &Base64UrlSafeData) -> Result<(), Error> {
@@ -3,9 +3,13 @@ pub use data_services::*;
 pub use db::*;
 pub use model::*;
 pub use session::*;
+pub use stateless_challenge::*;
+pub use tenant::*;
 
 pub mod cache;
 pub mod data_services;
 pub mod db;
 pub mod model;
 pub mod session;
+pub mod stateless_challenge;
+pub mod tenant;
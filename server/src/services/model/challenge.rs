@@ -1,14 +1,63 @@
 /// This module enables tracking Challenge values to ensure
-/// they follow a 'use once' strategy.  
+/// they follow a 'use once' strategy.
 ///
 /// The expectation is that [DataServices] provides methods for atomically
 /// creating and storing a new challenge.  And subsequently 'using' the challenge.
 ///
+/// Challenges also expire.  The lifetime is the ceremony's policy timeout plus
+/// a grace period (see [WebauthnPolicy](crate::webauthn::model::WebauthnPolicy)).
+/// The times are stored as BSON dates so that the TTL index on `expiresAt`
+/// lets MongoDB purge stale challenges.
+///
+/// Each challenge is bound to the ceremony, user, RP and session it was issued
+/// for, and is only accepted from a response with the same [ChallengeBinding].
+///
 use crate::utils::make_id;
 use base64urlsafedata::Base64UrlSafeData;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime;
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ceremony {
+    /// Registration: `navigator.credentials.create()`
+    Create,
+    /// Authentication: `navigator.credentials.get()`
+    Get,
+}
+
+/// What a challenge was issued for.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeBinding {
+    pub ceremony: Ceremony,
+    /// The [UserEntity](crate::webauthn::model::UserEntity) id
+    pub user_handle: Option<Base64UrlSafeData>,
+    pub rp_id: String,
+    /// The id of the [Session](crate::services::Session) that holds the challenge
+    pub session_id: Base64UrlSafeData,
+}
+
+impl ChallengeBinding {
+    /// Returns the name of the first attribute that differs, if any.
+    pub fn mismatch(&self, other: &ChallengeBinding) -> Option<&'static str> {
+        if self.ceremony != other.ceremony {
+            return Some("ceremony");
+        }
+        if self.user_handle != other.user_handle {
+            return Some("user");
+        }
+        if self.rp_id != other.rp_id {
+            return Some("rp_id");
+        }
+        if self.session_id != other.session_id {
+            return Some("session");
+        }
+        None
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Challenge {
@@ -16,30 +65,67 @@ pub struct Challenge {
     pub value: Base64UrlSafeData,
     /// Used suggests that a client has returned the challenge
     pub used: bool,
-    /// Not currently used
-    pub ttl: isize,
+    pub binding: ChallengeBinding,
+    /// The time the challenge was issued
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created: DateTime<Utc>,
+    /// The challenge is refused after this time
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
     /// The time the challenge was 'used'
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "optional_bson_datetime"
+    )]
     pub used_time: Option<DateTime<Utc>>,
 }
 
-impl Default for Challenge {
-    fn default() -> Self {
-        Self::new()
+/// Like [chrono_datetime_as_bson_datetime], for a time that may not be set
+mod optional_bson_datetime {
+    use chrono::{DateTime, Utc};
+    use mongodb::bson;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value.map(bson::DateTime::from_chrono).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Option::<bson::DateTime>::deserialize(deserializer)?;
+        Ok(value.map(|value| value.to_chrono()))
     }
 }
 
 impl Challenge {
     /// Generate a new challenge value from a cryptographically random value.
-    pub fn new() -> Self {
+    pub fn new(binding: ChallengeBinding, ttl: Duration) -> Self {
+        Self::from_value(&Base64UrlSafeData(make_id(32).unwrap()), binding, ttl)
+    }
+
+    /// Create a new Challenge from an existing random value
+    pub fn from_value(value: &Base64UrlSafeData, binding: ChallengeBinding, ttl: Duration) -> Self {
+        let created = Utc::now();
         Self {
-            value: Base64UrlSafeData(make_id(32).unwrap()),
+            value: value.clone(),
             used: false,
-            ttl: 0,
+            binding,
+            created,
+            expires_at: created + ttl,
             used_time: None,
         }
     }
 
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.expires_at
+    }
+
     /// Mark the challenge as used.  Includes setting the UTC time that this happens.
     pub fn mark_used(&self) -> Self {
         let mut new = self.clone();
@@ -49,14 +135,70 @@ impl Challenge {
     }
 }
 
-/// Create a new Challenge from an existing randome value
-impl From<&Base64UrlSafeData> for Challenge {
-    fn from(value: &Base64UrlSafeData) -> Self {
-        Self {
-            value: value.clone(),
-            used: false,
-            ttl: 0,
-            used_time: None,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding() -> ChallengeBinding {
+        ChallengeBinding {
+            ceremony: Ceremony::Get,
+            user_handle: Some(Base64UrlSafeData(vec![1, 2, 3])),
+            rp_id: "localhost".to_string(),
+            session_id: Base64UrlSafeData(vec![4, 5, 6]),
         }
     }
+
+    #[test]
+    fn test_fresh() {
+        let challenge = Challenge::new(binding(), Duration::minutes(5));
+        assert!(!challenge.is_expired());
+        assert_eq!(
+            challenge.expires_at - challenge.created,
+            Duration::minutes(5)
+        );
+    }
+
+    #[test]
+    fn test_used_time_is_a_date() {
+        let challenge = Challenge::new(binding(), Duration::minutes(5)).mark_used();
+        let doc = mongodb::bson::to_document(&challenge).expect("oops");
+        assert!(matches!(
+            doc.get("usedTime"),
+            Some(mongodb::bson::Bson::DateTime(_))
+        ));
+        let read: Challenge = mongodb::bson::from_document(doc).expect("oops");
+        assert_eq!(
+            read.used_time.map(|time| time.timestamp_millis()),
+            challenge.used_time.map(|time| time.timestamp_millis())
+        );
+    }
+
+    #[test]
+    fn test_expired() {
+        let mut challenge = Challenge::new(binding(), Duration::minutes(5));
+        challenge.expires_at = Utc::now() - Duration::seconds(1);
+        assert!(challenge.is_expired());
+    }
+
+    #[test]
+    fn test_binding_mismatch() {
+        let issued = binding();
+        assert_eq!(issued.mismatch(&binding()), None);
+
+        let mut other = binding();
+        other.ceremony = Ceremony::Create;
+        assert_eq!(issued.mismatch(&other), Some("ceremony"));
+
+        let mut other = binding();
+        other.user_handle = Some(Base64UrlSafeData(vec![9]));
+        assert_eq!(issued.mismatch(&other), Some("user"));
+
+        let mut other = binding();
+        other.rp_id = "example.com".to_string();
+        assert_eq!(issued.mismatch(&other), Some("rp_id"));
+
+        let mut other = binding();
+        other.session_id = Base64UrlSafeData(vec![9]);
+        assert_eq!(issued.mismatch(&other), Some("session"));
+    }
 }
@@ -0,0 +1,26 @@
+/// The history of a tenant's config.  Each change to the config is stored
+/// with the full config it produced, so that an admin can see who changed
+/// what, and roll back to an earlier version.
+///
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::AppConfig, utils::ValueChange};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigChange {
+    pub tenant: String,
+    /// The version of `config`
+    pub version: u64,
+    /// The admin that made the change.  None when the server created the
+    /// config from the env.
+    pub changed_by: Option<String>,
+    pub changed_at: DateTime<Utc>,
+    /// The version restored, if the change is a rollback
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rolled_back_to: Option<u64>,
+    /// What changed from the previous version
+    pub diff: Vec<ValueChange>,
+    pub config: AppConfig,
+}
@@ -1,5 +1,9 @@
 pub use challenge::*;
+pub use config_change::*;
+pub use policy_dry_run::*;
 pub use user::*;
 
 pub mod challenge;
+pub mod config_change;
+pub mod policy_dry_run;
 pub mod user;
@@ -0,0 +1,23 @@
+/// The effect a policy patch would have.  A dry run reports the violations
+/// of the patched policy instead of refusing it, and nothing is saved.
+///
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    utils::ValueChange,
+    webauthn::model::{PolicyViolation, WebauthnPolicy},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyDryRun {
+    /// The version of the config the patch was applied to
+    pub version: u64,
+    /// True if the patch would be saved
+    pub valid: bool,
+    pub violations: Vec<PolicyViolation>,
+    /// What would change in the policy
+    pub diff: Vec<ValueChange>,
+    /// The policy as it would be
+    pub policy: WebauthnPolicy,
+}
@@ -5,6 +5,14 @@ use serde::{Deserialize, Serialize};
 
 use crate::webauthn::model::UserEntity;
 
+/// Roles that can be granted to a stored [User].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Allowed to use the `/api/admin` endpoints
+    Admin,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct User {
@@ -13,6 +21,9 @@ pub struct User {
     pub display_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub credentials: Option<Vec<Base64UrlSafeData>>,
+    /// Users stored before roles existed have none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<Role>,
 }
 
 impl User {
@@ -23,6 +34,14 @@ impl User {
             display_name: self.display_name.clone(),
         }
     }
+
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.contains(&role)
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.has_role(Role::Admin)
+    }
 }
 
 impl From<&UserEntity> for User {
@@ -32,6 +51,27 @@ impl From<&UserEntity> for User {
             name: user.name.clone(),
             display_name: user.display_name.clone(),
             credentials: None,
+            roles: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_user_has_no_roles() {
+        let json = r#"{"id": null, "name": "bob", "displayName": "Bob"}"#;
+        let user: User = serde_json::from_str(json).expect("failed to parse");
+        assert!(user.roles.is_empty());
+        assert!(!user.is_admin());
+    }
+
+    #[test]
+    fn test_admin_role() {
+        let json = r#"{"id": null, "name": "bob", "displayName": "Bob", "roles": ["admin"]}"#;
+        let user: User = serde_json::from_str(json).expect("failed to parse");
+        assert!(user.is_admin());
+    }
+}
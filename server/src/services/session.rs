@@ -14,6 +14,7 @@
 //!
 use actix_web::HttpRequest;
 use base64urlsafedata::Base64UrlSafeData;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 
 use crate::{errors::Error, services::DataServices, utils::make_id};
@@ -83,6 +84,28 @@ impl Session {
         }
     }
 
+    /// Store a time as an RFC 3339 string
+    pub fn insert_time(&mut self, key: &str, time: &DateTime<Utc>) {
+        self.insert(key, &time.to_rfc3339());
+    }
+
+    pub fn as_time(&self, name: &str) -> Result<DateTime<Utc>, Error> {
+        let val = self.as_str(name)?;
+        let time = DateTime::parse_from_rfc3339(&val)
+            .map_err(|_| Error::SessionError(format!("Failed to parse {} from session", name)))?;
+        Ok(time.with_timezone(&Utc))
+    }
+
+    /// True once an assertion ceremony has completed for this session
+    pub fn is_authenticated(&self) -> bool {
+        self.get("authenticated").as_deref() == Some("true")
+    }
+
+    /// True if the assertion that authenticated this session verified the user
+    pub fn is_user_verified(&self) -> bool {
+        self.get("user_verified").as_deref() == Some("true")
+    }
+
     pub async fn put_session(&self, service: &DataServices) -> Result<(), Error> {
         log::trace!("Saving session to storage");
         service.put_session(&self.id, &self.entries).await
@@ -0,0 +1,278 @@
+//! Stateless challenges.
+//!
+//! Rather than storing each challenge, the challenge value is itself a token
+//! that the server can verify:
+//!
+//! - VERSION: 1
+//! - CEREMONY: 1 (0 = create, 1 = get)
+//! - EXPIRY: 8 (big endian milliseconds since the epoch)
+//! - NONCE: 16
+//! - USER HANDLE LENGTH: 1, followed by the user handle
+//! - SESSION ID LENGTH: 1, followed by the session id
+//! - TAG: 32, HMAC-SHA256 over the bytes above and the RP id
+//!
+//! The RP id is only covered by the tag, so a token issued for one RP does not
+//! verify for another.  Replay is prevented by [Cache::consume_nonce](super::Cache::consume_nonce).
+//!
+use base64urlsafedata::Base64UrlSafeData;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use ring::hmac;
+
+use super::{Ceremony, Challenge, ChallengeBinding};
+use crate::{errors::Error, utils::make_id};
+
+const TOKEN_VERSION: u8 = 1;
+const NONCE_LEN: usize = 16;
+const TAG_LEN: usize = 32;
+
+/// The verified content of a challenge token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChallengeToken {
+    pub nonce: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+    pub binding: ChallengeBinding,
+}
+
+impl ChallengeToken {
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.expires_at
+    }
+}
+
+#[derive(Clone)]
+pub struct StatelessChallenges {
+    key: Vec<u8>,
+}
+
+impl StatelessChallenges {
+    pub fn new(key: &[u8]) -> Self {
+        Self { key: key.to_vec() }
+    }
+
+    /// Issue a token for `binding` that expires after `ttl`.  The returned
+    /// [Challenge] is not stored anywhere.
+    pub fn issue(&self, binding: ChallengeBinding, ttl: Duration) -> Result<Challenge, Error> {
+        let nonce = make_id(NONCE_LEN)?;
+        let created = Utc::now();
+        let expires_at = created + ttl;
+
+        let mut token = encode_payload(&nonce, &expires_at, &binding)?;
+        let tag = self.sign(&token, &binding.rp_id);
+        token.extend_from_slice(tag.as_ref());
+
+        Ok(Challenge {
+            value: Base64UrlSafeData(token),
+            used: false,
+            binding,
+            created,
+            expires_at,
+            used_time: None,
+        })
+    }
+
+    /// Check the tag and decode the token.  Fails with [Error::ChallengeNotFound]
+    /// if the token was not issued by this server for `rp_id`.  Expiry and the
+    /// binding are left to the caller.
+    pub fn verify(&self, value: &Base64UrlSafeData, rp_id: &str) -> Result<ChallengeToken, Error> {
+        let bytes: &[u8] = value.as_ref();
+        if bytes.len() < TAG_LEN {
+            return Err(Error::ChallengeNotFound);
+        }
+        let (payload, tag) = bytes.split_at(bytes.len() - TAG_LEN);
+
+        let key = hmac::Key::new(hmac::HMAC_SHA256, &self.key);
+        hmac::verify(&key, &signed_data(payload, rp_id), tag)
+            .map_err(|_| Error::ChallengeNotFound)?;
+
+        decode_payload(payload, rp_id)
+    }
+
+    fn sign(&self, payload: &[u8], rp_id: &str) -> hmac::Tag {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, &self.key);
+        hmac::sign(&key, &signed_data(payload, rp_id))
+    }
+}
+
+fn signed_data(payload: &[u8], rp_id: &str) -> Vec<u8> {
+    payload
+        .iter()
+        .chain(rp_id.as_bytes().iter())
+        .copied()
+        .collect()
+}
+
+fn encode_payload(
+    nonce: &[u8],
+    expires_at: &DateTime<Utc>,
+    binding: &ChallengeBinding,
+) -> Result<Vec<u8>, Error> {
+    let user_handle: &[u8] = match &binding.user_handle {
+        Some(user_handle) => user_handle.as_ref(),
+        None => &[],
+    };
+    let session_id: &[u8] = binding.session_id.as_ref();
+    let user_handle_len = u8::try_from(user_handle.len())
+        .map_err(|_| Error::BuildError("user handle is too long".to_string()))?;
+    let session_id_len = u8::try_from(session_id.len())
+        .map_err(|_| Error::BuildError("session id is too long".to_string()))?;
+
+    let mut payload = vec![TOKEN_VERSION, ceremony_to_byte(binding.ceremony)];
+    payload.extend_from_slice(&expires_at.timestamp_millis().to_be_bytes());
+    payload.extend_from_slice(nonce);
+    payload.push(user_handle_len);
+    payload.extend_from_slice(user_handle);
+    payload.push(session_id_len);
+    payload.extend_from_slice(session_id);
+    Ok(payload)
+}
+
+fn decode_payload(payload: &[u8], rp_id: &str) -> Result<ChallengeToken, Error> {
+    let mut reader = Reader(payload);
+    if reader.take(1)?[0] != TOKEN_VERSION {
+        return Err(Error::ChallengeNotFound);
+    }
+    let ceremony = ceremony_from_byte(reader.take(1)?[0])?;
+
+    let mut expiry: [u8; 8] = [0; 8];
+    expiry.copy_from_slice(reader.take(8)?);
+    let expires_at = Utc
+        .timestamp_millis_opt(i64::from_be_bytes(expiry))
+        .single()
+        .ok_or(Error::ChallengeNotFound)?;
+
+    let nonce = reader.take(NONCE_LEN)?.to_vec();
+
+    let len = reader.take(1)?[0] as usize;
+    let user_handle = match reader.take(len)? {
+        [] => None,
+        user_handle => Some(Base64UrlSafeData(user_handle.to_vec())),
+    };
+
+    let len = reader.take(1)?[0] as usize;
+    let session_id = Base64UrlSafeData(reader.take(len)?.to_vec());
+
+    if !reader.0.is_empty() {
+        return Err(Error::ChallengeNotFound);
+    }
+
+    Ok(ChallengeToken {
+        nonce,
+        expires_at,
+        binding: ChallengeBinding {
+            ceremony,
+            user_handle,
+            rp_id: rp_id.to_string(),
+            session_id,
+        },
+    })
+}
+
+fn ceremony_to_byte(ceremony: Ceremony) -> u8 {
+    match ceremony {
+        Ceremony::Create => 0,
+        Ceremony::Get => 1,
+    }
+}
+
+fn ceremony_from_byte(byte: u8) -> Result<Ceremony, Error> {
+    match byte {
+        0 => Ok(Ceremony::Create),
+        1 => Ok(Ceremony::Get),
+        _ => Err(Error::ChallengeNotFound),
+    }
+}
+
+/// Reads fixed length fields from the front of a slice
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(Error::ChallengeNotFound);
+        }
+        let (field, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding() -> ChallengeBinding {
+        ChallengeBinding {
+            ceremony: Ceremony::Create,
+            user_handle: Some(Base64UrlSafeData(vec![7; 32])),
+            rp_id: "localhost".to_string(),
+            session_id: Base64UrlSafeData(vec![9; 16]),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<(), Error> {
+        let challenges = StatelessChallenges::new(&[1; 32]);
+        let challenge = challenges.issue(binding(), Duration::minutes(5))?;
+
+        let token = challenges.verify(&challenge.value, "localhost")?;
+        assert_eq!(token.binding, binding());
+        assert_eq!(
+            token.expires_at.timestamp_millis(),
+            challenge.expires_at.timestamp_millis()
+        );
+        assert!(!token.is_expired());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nonces_differ() -> Result<(), Error> {
+        let challenges = StatelessChallenges::new(&[1; 32]);
+        let first = challenges.issue(binding(), Duration::minutes(5))?;
+        let second = challenges.issue(binding(), Duration::minutes(5))?;
+        assert_ne!(first.value, second.value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expired() -> Result<(), Error> {
+        let challenges = StatelessChallenges::new(&[1; 32]);
+        let challenge = challenges.issue(binding(), Duration::minutes(-1))?;
+        let token = challenges.verify(&challenge.value, "localhost")?;
+        assert!(token.is_expired());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampered() -> Result<(), Error> {
+        let challenges = StatelessChallenges::new(&[1; 32]);
+        let challenge = challenges.issue(binding(), Duration::minutes(5))?;
+
+        let mut bytes = challenge.value.0.clone();
+        // Flip the ceremony from create to get
+        bytes[1] = 1;
+        let result = challenges.verify(&Base64UrlSafeData(bytes), "localhost");
+        assert!(matches!(result, Err(Error::ChallengeNotFound)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_rp_or_key() -> Result<(), Error> {
+        let challenges = StatelessChallenges::new(&[1; 32]);
+        let challenge = challenges.issue(binding(), Duration::minutes(5))?;
+
+        let result = challenges.verify(&challenge.value, "example.com");
+        assert!(matches!(result, Err(Error::ChallengeNotFound)));
+
+        let other = StatelessChallenges::new(&[2; 32]);
+        let result = other.verify(&challenge.value, "localhost");
+        assert!(matches!(result, Err(Error::ChallengeNotFound)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated() {
+        let challenges = StatelessChallenges::new(&[1; 32]);
+        let result = challenges.verify(&Base64UrlSafeData(vec![1, 2, 3]), "localhost");
+        assert!(matches!(result, Err(Error::ChallengeNotFound)));
+    }
+}
@@ -0,0 +1,46 @@
+//! Per-tenant services for request handlers.
+//!
+//! A deployment can serve several relying parties.  Each tenant has its own
+//! [AppConfig](crate::config::AppConfig), users, credentials, challenges and
+//! sessions.  A request is for the tenant named by a `/t/{tenant}` path
+//! prefix, else the tenant with the request's host in its `hosts`, else the
+//! default tenant.  See [DataServices::for_request].
+//!
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use futures::future::LocalBoxFuture;
+use std::ops::Deref;
+
+use super::DataServices;
+use crate::errors::Error;
+
+/// The name of the tenant in a `/t/{tenant}` route
+pub const TENANT_PATH_PARAM: &str = "tenant";
+
+/// [DataServices] scoped to the tenant of the request.  Handlers take this in
+/// place of `web::Data<DataServices>`.
+pub struct TenantServices(DataServices);
+
+impl Deref for TenantServices {
+    type Target = DataServices;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for TenantServices {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let data = req
+                .app_data::<web::Data<DataServices>>()
+                .cloned()
+                .ok_or_else(|| Error::ServiceError("Services not available".to_string()))?;
+            let services = data.for_request(&req).await?;
+            Ok(TenantServices(services))
+        })
+    }
+}
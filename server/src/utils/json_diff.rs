@@ -0,0 +1,64 @@
+/// The differences between two JSON values, by the dotted path of each
+/// changed value.  Objects are compared key by key, anything else as a whole.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueChange {
+    pub path: String,
+    /// Null if the value was added
+    pub from: Value,
+    /// Null if the value was removed
+    pub to: Value,
+}
+
+pub fn json_diff(from: &Value, to: &Value) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+    diff_at("", from, to, &mut changes);
+    changes
+}
+
+fn diff_at(path: &str, from: &Value, to: &Value, changes: &mut Vec<ValueChange>) {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            let mut keys: Vec<&String> = from.keys().chain(to.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                let from = from.get(key).unwrap_or(&Value::Null);
+                let to = to.get(key).unwrap_or(&Value::Null);
+                diff_at(&path, from, to, changes);
+            }
+        }
+        _ if from != to => changes.push(ValueChange {
+            path: path.to_string(),
+            from: from.clone(),
+            to: to.clone(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_diff() {
+        let from = json!({"rp": {"id": "localhost", "prf": false}, "algs": [-7]});
+        let to = json!({"rp": {"id": "example.com", "prf": false}, "algs": [-7, -8], "x": 1});
+        let changes = json_diff(&from, &to);
+        let paths: Vec<&str> = changes.iter().map(|change| change.path.as_str()).collect();
+        assert_eq!(paths, vec!["algs", "rp.id", "x"]);
+        assert_eq!(changes[1].from, json!("localhost"));
+        assert_eq!(changes[2].from, Value::Null);
+
+        assert!(json_diff(&from, &from).is_empty());
+    }
+}
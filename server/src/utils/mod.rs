@@ -1,7 +1,11 @@
 pub use b64::*;
 pub use from_session::*;
+pub use json_diff::*;
 pub use make_id::make_id;
+pub use public_suffix::*;
 
 pub mod b64;
 pub mod from_session;
+pub mod json_diff;
 pub mod make_id;
+pub mod public_suffix;
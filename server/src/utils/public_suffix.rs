@@ -0,0 +1,67 @@
+/// RP id checks against the Public Suffix List.  The list is compiled in from
+/// [data/public_suffix_list.dat](../../data/public_suffix_list.dat), so no
+/// network access is needed.  Refresh the file from
+/// https://publicsuffix.org/list/public_suffix_list.dat now and then.
+use publicsuffix::{List, Psl};
+use std::sync::OnceLock;
+
+static LIST: OnceLock<List> = OnceLock::new();
+
+fn list() -> &'static List {
+    LIST.get_or_init(|| {
+        include_str!("../../data/public_suffix_list.dat")
+            .parse()
+            .expect("Failed to parse the public suffix list")
+    })
+}
+
+/// True if `rp_id` can be used as an RP id: `localhost`, or a domain that is
+/// not itself a public suffix such as `com` or `co.uk`.
+pub fn is_registrable_rp_id(rp_id: &str) -> bool {
+    rp_id == "localhost" || list().domain(rp_id.as_bytes()).is_some()
+}
+
+/// True if `host` is `rp_id` or one of its subdomains
+pub fn is_within_rp_id(host: &str, rp_id: &str) -> bool {
+    host == rp_id || host.ends_with(&format!(".{}", rp_id))
+}
+
+/// The label just before the public suffix, e.g. `example` for
+/// `www.example.co.uk`.  Related origins are counted by these labels.
+pub fn registrable_label(host: &str) -> Option<String> {
+    let domain = list().domain(host.as_bytes())?;
+    let bytes = domain.as_bytes();
+    let label = &bytes[..bytes.len() - domain.suffix().as_bytes().len() - 1];
+    Some(String::from_utf8_lossy(label).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registrable_rp_id() {
+        assert!(is_registrable_rp_id("localhost"));
+        assert!(is_registrable_rp_id("example.com"));
+        assert!(is_registrable_rp_id("login.example.co.uk"));
+        assert!(!is_registrable_rp_id("com"));
+        assert!(!is_registrable_rp_id("co.uk"));
+        assert!(!is_registrable_rp_id("github.io"));
+    }
+
+    #[test]
+    fn test_within_rp_id() {
+        assert!(is_within_rp_id("example.com", "example.com"));
+        assert!(is_within_rp_id("app.example.com", "example.com"));
+        assert!(!is_within_rp_id("badexample.com", "example.com"));
+        assert!(!is_within_rp_id("example.com", "app.example.com"));
+    }
+
+    #[test]
+    fn test_registrable_label() {
+        let label = registrable_label("www.example.co.uk");
+        assert_eq!(label.as_deref(), Some("example"));
+        assert_eq!(registrable_label("example.com").as_deref(), Some("example"));
+        assert_eq!(registrable_label("co.uk"), None);
+    }
+}
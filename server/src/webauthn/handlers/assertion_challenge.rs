@@ -5,11 +5,15 @@
 /// to send to the WebAuthn Authenticator (via the WebAuthn Client)
 ///
 use crate::webauthn::model::{PublicKeyCredentialRequestOptions, UserEntity};
-use crate::{errors::Error, services::Session, DataServices};
+use crate::{
+    errors::Error,
+    services::{Ceremony, ChallengeBinding, Session},
+    TenantServices,
+};
 use actix_web::{web, HttpRequest, HttpResponse};
 
 pub async fn assertion_challenge(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     request: web::Json<UserEntity>,
     _req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
@@ -30,30 +34,38 @@ pub async fn assertion_challenge(
         }
     };
 
-    // Get the credential id's for this user, or return 403
-    let credential = match service.get_user_credential(&user.name).await? {
-        Some(c) => c,
-        None => {
-            log::trace!("Credential not found for user: {}", request.name);
-            return Ok(HttpResponse::Forbidden().json(format!(
-                r#"{{"message": "Credential not found: {}"}}"#,
-                request.name
-            )));
-        }
-    };
+    // Get the credentials for this user, or return 403
+    let credentials = service.get_user_credentials(&user.name).await?;
+    if credentials.is_empty() {
+        log::trace!("Credential not found for user: {}", request.name);
+        return Ok(HttpResponse::Forbidden().json(format!(
+            r#"{{"message": "Credential not found: {}"}}"#,
+            request.name
+        )));
+    }
+
+    // The session for the next step (response) holds the challenge.
+    let session = Session::default();
 
     // Create a challenge, and save it.
-    let challenge = service.create_new_challenge().await?;
+    let binding = ChallengeBinding {
+        ceremony: Ceremony::Get,
+        user_handle: user.id.clone(),
+        rp_id: config.webauthn.rp.id.clone(),
+        session_id: session.id.clone(),
+    };
+    let challenge = service
+        .create_new_challenge(binding, config.webauthn.authentication_challenge_ttl())
+        .await?;
 
     // Create the PublicKey Creation Options
     let pk_options = PublicKeyCredentialRequestOptions::try_from((
         &config.webauthn,
-        &credential,
+        credentials.as_slice(),
         &challenge.value,
     ))?;
 
-    // Create a session for the next step (response).
-    let session = Session::default()
+    let session = session
         .with("name", &user.name)
         .with("challenge", &pk_options.challenge.to_string());
     session.put_session(&service).await?;
@@ -3,15 +3,22 @@
 /// in the [AssertionPublicKeyCredential].  
 ///  
 use actix_web::{web, HttpRequest, HttpResponse};
+use base64urlsafedata::Base64UrlSafeData;
+use chrono::Utc;
 
 use crate::{
     errors::Error,
-    webauthn::model::{AssertionPublicKeyCredential, PublicKeyCredentialType},
-    DataServices, Session,
+    services::{Ceremony, ChallengeBinding},
+    webauthn::model::{
+        AssertionPublicKeyCredential, DeviceBoundRequirement, PublicKeyCredentialType,
+    },
+    Session, TenantServices,
 };
 
+use super::{LARGE_BLOB_NOT_WRITTEN_WARNING, NOT_BACKED_UP_WARNING};
+
 pub async fn assertion_response(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     credential: web::Json<AssertionPublicKeyCredential>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
@@ -33,7 +40,17 @@ pub async fn assertion_response(
     // by register_challenge_request
     // The challenge should have been stored as Base64.  Decode it
     let challenge = session.as_b64("challenge")?;
-    if let Err(err) = service.use_challenge(&challenge).await {
+    let name = session.as_str("name")?;
+    let user = service.get_stored_user(&name).await?;
+
+    // The challenge must have been issued to authenticate this user, in this session
+    let binding = ChallengeBinding {
+        ceremony: Ceremony::Get,
+        user_handle: user.as_ref().and_then(|u| u.id.clone()),
+        rp_id: config.webauthn.rp.id.clone(),
+        session_id: session.id.clone(),
+    };
+    if let Err(err) = service.use_challenge(&challenge, &binding).await {
         match err {
             Error::ChallengeNotFound => {
                 log::trace!("Provided challenge was not found");
@@ -45,6 +62,17 @@ pub async fn assertion_response(
                     HttpResponse::Forbidden().json(r#"{ "message": "Challenge is already used" }"#)
                 );
             }
+            Error::ChallengeExpired => {
+                log::trace!("Provided challenge has expired");
+                return Ok(
+                    HttpResponse::Forbidden().json(r#"{ "message": "Challenge has expired" }"#)
+                );
+            }
+            Error::ChallengeMismatch(attribute) => {
+                log::trace!("Provided challenge was issued for another {}", &attribute);
+                return Ok(HttpResponse::Forbidden()
+                    .json(r#"{ "message": "Challenge was not issued for this request" }"#));
+            }
             _ => {
                 return Ok(HttpResponse::InternalServerError()
                     .json(r#"{ "message": "Error getting session" }"#))
@@ -52,8 +80,6 @@ pub async fn assertion_response(
         }
     }
 
-    // let name = session.as_str("name")?;
-
     // ------------ 7.1 RP verification ----------------//
     // Steps 1 - 6 are either performed in javascript before
     // postint.  Start with step 7
@@ -65,8 +91,28 @@ pub async fn assertion_response(
             .json(r#"{ "message": "response type must be 'public-key" }"#));
     }
 
-    // Get the credential from the data store
-    //let cred = service.get_user_credential(&name).await?.unwrap();
+    // 7.2 step 6: The credential must belong to the user that started the
+    // ceremony, since the session is authenticated as that user.  If the
+    // authenticator returned a user handle, it must be that user's too.
+    let owned = match &user {
+        Some(user) => user
+            .credentials
+            .as_ref()
+            .is_some_and(|ids| ids.contains(&credential.id)),
+        None => false,
+    };
+    if !owned {
+        log::trace!("Credential does not belong to user: {}", &name);
+        return Ok(HttpResponse::NotFound().json(r#"{ "message": "Credential not found" }"#));
+    }
+    if let Some(user_handle) = &credential.response.user_handle {
+        let user_handle = Base64UrlSafeData::try_from(user_handle.as_str())
+            .map_err(|_| Error::Base64UrlSafeDataError)?;
+        if Some(user_handle) != binding.user_handle {
+            log::trace!("User handle does not belong to user: {}", &name);
+            return Ok(HttpResponse::Unauthorized().json(r#"{ "message": "bad user handle" }"#));
+        }
+    }
     let result = service.get_credential(&credential.id).await?;
     if result.is_none() {
         return Ok(HttpResponse::NotFound().json(r#"{ "message": "Credential not found" }"#));
@@ -74,9 +120,14 @@ pub async fn assertion_response(
     let cred = result.unwrap();
 
     // Verify the response
-    let result = credential
-        .response
-        .verify(&config.webauthn, &challenge, &cred);
+    let client_extension_results = &credential.client_extension_results;
+    let response = &credential.response;
+    let result = response.verify(
+        &config.webauthn,
+        &challenge,
+        &cred,
+        client_extension_results,
+    );
 
     match result {
         Err(err) => match err {
@@ -88,16 +139,71 @@ pub async fn assertion_response(
                 log::trace!("Origin mismatch");
                 Ok(HttpResponse::Unauthorized().json(r#"{ "message": "bad origin" }"#))
             }
+            Error::CrossOriginNotAllowed | Error::BadTopOrigin => {
+                log::trace!("{}", err);
+                Ok(HttpResponse::Unauthorized()
+                    .json(r#"{ "message": "Cross-origin policy not met" }"#))
+            }
+            Error::UserNotPresent | Error::UserNotVerified => {
+                log::trace!("{}", err);
+                Ok(HttpResponse::Unauthorized()
+                    .json(r#"{ "message": "User verification policy not met" }"#))
+            }
             _ => Err(err),
         },
         Ok(credential) => {
+            // The policy may have changed since the credential was registered
+            let device_bound = !credential.is_backup_eligible();
+            if config.webauthn.rp.device_bound == DeviceBoundRequirement::All && !device_bound {
+                log::trace!("Synced credential refused by policy");
+                return Ok(HttpResponse::Forbidden()
+                    .json(r#"{ "message": "Synced credentials are not allowed" }"#));
+            }
+
+            // The session becomes the login session for the api.  Unless the
+            // policy requires it, the user may not have been verified, so only
+            // a UV assertion counts as the last user verification.
+            let now = Utc::now();
+            let user_verified = response.is_user_verified()?;
             session.insert("authenticated", "true");
+            session.insert_time("authenticated_at", &now);
+            session.insert("user_verified", &user_verified.to_string());
+            if user_verified {
+                session.insert_time("user_verified_at", &now);
+            }
+            session.insert("device_bound", &device_bound.to_string());
+
+            // A large blob write was requested.  The assertion is still good
+            // if the write failed, so the client is told rather than refused.
+            let large_blob_write = session.get("large_blob_write");
+            session.put_session(&service).await?;
 
             // Update the credential so that the counter and date stuff is right.
             service.update_credential(&credential).await?;
+
+            if let Some(id) = large_blob_write {
+                let written = match id == credential.id.to_string() {
+                    true => client_extension_results.verify_large_blob_written(),
+                    false => Err(Error::LargeBlobError("wrong credential".to_string())),
+                };
+                if let Err(err) = written {
+                    log::trace!("{}", err);
+                    return Ok(HttpResponse::Ok()
+                        .insert_header(session.to_header())
+                        .json(LARGE_BLOB_NOT_WRITTEN_WARNING));
+                }
+            }
+
+            let body = if config.webauthn.rp.warn_not_backed_up
+                && service.only_credential_not_backed_up(&name).await?
+            {
+                NOT_BACKED_UP_WARNING
+            } else {
+                r#"{"status": "ok"}"#
+            };
             Ok(HttpResponse::Ok()
                 .insert_header(session.to_header())
-                .json(r#"{"status": "ok"}"#))
+                .json(body))
         }
     }
 }
@@ -1,44 +1,95 @@
 //use actix_session::Session;
 use actix_web::{web, HttpRequest, HttpResponse};
 
-use crate::webauthn::model::{PublicKeyCredentialCreationOptions, UserEntity};
-use crate::{errors::Error, services::Session, DataServices};
+use crate::webauthn::model::{
+    PublicKeyCredentialCreationOptionsBuilder, PublicKeyCredentialDiscriptor, UserEntity,
+};
+use crate::{
+    api::authenticated_session,
+    errors::Error,
+    services::{Ceremony, ChallengeBinding, Session},
+    TenantServices,
+};
+
+/// Carries `ADMIN_BOOTSTRAP_TOKEN` when registering the bootstrap admin
+pub const BOOTSTRAP_TOKEN_HEADER: &str = "X-BOOTSTRAP-TOKEN";
 
 pub async fn creation_challenge(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     request: web::Json<UserEntity>,
-    _req: HttpRequest,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     log::trace!("Registration Request: {:?}", &request);
     let config = service.get_config().await?;
 
-    // See if this user already exists.  If so, return 403
-    if service.check_user(&request.name).await? {
-        // Return already registered
-        log::trace!("User already exists: {}", request.name);
-        return Ok(
-            HttpResponse::Forbidden().body(format!("User already registered: {}", request.name))
-        );
+    // A registered user can only add a credential from a session signed in as
+    // that user.  Otherwise return 403
+    let signed_in_as = match authenticated_session(&service, &req).await {
+        Ok((_, name)) => Some(name),
+        Err(_) => None,
+    };
+    // The bootstrap admin can only be registered with the bootstrap token
+    let token = req
+        .headers()
+        .get(BOOTSTRAP_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let bootstrap = match service.check_bootstrap_claim(&request.name, token).await {
+        Ok(bootstrap) => bootstrap,
+        Err(Error::BootstrapTokenRequired(_)) => {
+            log::trace!("Bootstrap user without a valid token: {}", request.name);
+            return Ok(HttpResponse::Forbidden()
+                .json(r#"{ "message": "A valid bootstrap token is required" }"#));
+        }
+        Err(err) => return Err(err),
+    };
+    let user = match service
+        .registering_user(&request, signed_in_as.as_deref())
+        .await?
+    {
+        Some(user) => user,
+        None => {
+            log::trace!("User already exists: {}", request.name);
+            return Ok(HttpResponse::Forbidden()
+                .body(format!("User already registered: {}", request.name)));
+        }
+    };
+
+    // The user's credentials are excluded, so that an authenticator is not
+    // registered twice
+    let mut exclude_credentials = Vec::new();
+    for credential in service.get_user_credentials(&user.name).await? {
+        exclude_credentials.push(PublicKeyCredentialDiscriptor::try_from(&credential)?);
     }
 
-    let user = request.into_inner();
+    // Create a session for the next step (response).
+    let session = Session::default();
 
     // Create a challenge, and save it.
-    let challenge = service.create_new_challenge().await?;
+    let binding = ChallengeBinding {
+        ceremony: Ceremony::Create,
+        user_handle: user.id.clone(),
+        rp_id: config.webauthn.rp.id.clone(),
+        session_id: session.id.clone(),
+    };
+    let challenge = service
+        .create_new_challenge(binding, config.webauthn.registration_challenge_ttl())
+        .await?;
 
     // Create the PublicKey Creation Options
-    let pk_options =
-        PublicKeyCredentialCreationOptions::try_from((&config.webauthn, &user, &challenge.value))?;
-
-    // Save the user
-    log::trace!("Saving user entity: {:?}", &pk_options.user);
-
-    service.add_user(&pk_options.user).await?;
+    let mut builder = PublicKeyCredentialCreationOptionsBuilder::from(&config.webauthn)
+        .with_challenge(&challenge.value)
+        .with_user(user.clone());
+    if !exclude_credentials.is_empty() {
+        builder = builder.with_exclude_credentials(exclude_credentials);
+    }
+    let pk_options = builder.build()?;
 
-    // Create a session for the next step (response).
-    let session = Session::default()
+    let mut session = session
         .with("name", &user.name)
         .with("challenge", &pk_options.challenge.to_string());
+    if bootstrap {
+        session = session.with("bootstrap", "true");
+    }
     session.put_session(&service).await?;
     let header = session.to_header();
     // Return the PK Options
@@ -1,14 +1,20 @@
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
 use base64urlsafedata::Base64UrlSafeData;
 
 use crate::{
     errors::Error,
-    webauthn::model::{CreationPublicKeyCredential, PublicKeyCredentialType},
-    DataServices, Session,
+    services::{Ceremony, ChallengeBinding},
+    webauthn::model::{
+        CreationPublicKeyCredential, DeviceBoundRequirement, LargeBlobRequirement,
+        PublicKeyCredentialType, ResidentKeyRequirement,
+    },
+    Session, TenantServices,
 };
 
+use super::{NOT_BACKED_UP_WARNING, NOT_DISCOVERABLE_WARNING};
+
 pub async fn creation_response(
-    service: web::Data<DataServices>,
+    service: TenantServices,
     credential: web::Json<CreationPublicKeyCredential>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
@@ -31,7 +37,17 @@ pub async fn creation_response(
     // by register_challenge_request
     // The challenge should have been stored as Base64.  Decode it
     let challenge = session.as_b64("challenge")?;
-    if let Err(err) = service.use_challenge(&challenge).await {
+    let name = session.as_str("name")?;
+
+    // The challenge must have been issued to register this user, in this session
+    let user_handle = service.get_stored_user(&name).await?.and_then(|u| u.id);
+    let binding = ChallengeBinding {
+        ceremony: Ceremony::Create,
+        user_handle,
+        rp_id: config.webauthn.rp.id.clone(),
+        session_id: session.id.clone(),
+    };
+    if let Err(err) = service.use_challenge(&challenge, &binding).await {
         match err {
             Error::ChallengeNotFound => {
                 log::trace!("Provided challenge was not found");
@@ -43,6 +59,17 @@ pub async fn creation_response(
                     HttpResponse::Forbidden().json(r#"{ "message": "Challenge is already used" }"#)
                 );
             }
+            Error::ChallengeExpired => {
+                log::trace!("Provided challenge has expired");
+                return Ok(
+                    HttpResponse::Forbidden().json(r#"{ "message": "Challenge has expired" }"#)
+                );
+            }
+            Error::ChallengeMismatch(attribute) => {
+                log::trace!("Provided challenge was issued for another {}", &attribute);
+                return Ok(HttpResponse::Forbidden()
+                    .json(r#"{ "message": "Challenge was not issued for this request" }"#));
+            }
             _ => {
                 return Ok(HttpResponse::InternalServerError()
                     .json(r#"{ "message": "Error getting session" }"#))
@@ -50,8 +77,6 @@ pub async fn creation_response(
         }
     }
 
-    let name = session.as_str("name")?;
-
     // ------------ 7.1 RP verification ----------------//
     // Steps 1 - 6 are either performed in javascript before
     // postint.  Start with step 7
@@ -74,6 +99,16 @@ pub async fn creation_response(
                 log::trace!("Origin mismatch");
                 return Ok(HttpResponse::Unauthorized().json(r#"{ "message": "bad origin" }"#));
             }
+            Error::CrossOriginNotAllowed | Error::BadTopOrigin => {
+                log::trace!("{}", err);
+                return Ok(HttpResponse::Unauthorized()
+                    .json(r#"{ "message": "Cross-origin policy not met" }"#));
+            }
+            Error::UserNotPresent | Error::UserNotVerified => {
+                log::trace!("{}", err);
+                return Ok(HttpResponse::Unauthorized()
+                    .json(r#"{ "message": "User verification policy not met" }"#));
+            }
             _ => {
                 log::trace!("Challenge: unexpected error: {}", &err.to_string());
                 return Err(err);
@@ -83,6 +118,31 @@ pub async fn creation_response(
 
     let auth_data = result.unwrap();
 
+    if config.webauthn.rp.device_bound == DeviceBoundRequirement::All
+        && auth_data.is_backup_eligible()
+    {
+        log::trace!("Synced credential refused by policy");
+        return Ok(HttpResponse::Forbidden()
+            .json(r#"{ "message": "Synced credentials are not allowed" }"#));
+    }
+
+    // The authenticator picks one of the offered algorithms, but the client
+    // does not enforce that
+    let alg = auth_data.credential_data()?.credential_public_key.alg;
+    if !alg.is_some_and(|alg| config.webauthn.registration.allows_alg(alg)) {
+        log::trace!("Credential algorithm refused by policy: {:?}", alg);
+        return Ok(HttpResponse::Forbidden()
+            .json(r#"{ "message": "Credential algorithm is not allowed" }"#));
+    }
+
+    // Authenticators without extension outputs default to the weakest level
+    let extensions = auth_data.extensions.clone().unwrap_or_default();
+    if let Err(err) = extensions.verify_credential_protection(&config.webauthn.registration) {
+        log::trace!("{}", err);
+        return Ok(HttpResponse::Forbidden()
+            .json(r#"{ "message": "Credential protection policy not met" }"#));
+    }
+
     // The response is valid.
     // The authData is returned from the verify function
     let id = Base64UrlSafeData(auth_data.credential_data()?.credential_id);
@@ -92,9 +152,56 @@ pub async fn creation_response(
         log::trace!("Credential ID is already used");
         return Ok(HttpResponse::Unauthorized().json(r#"{ "message": "credentialId in use" }"#));
     }
-    // Save the credential
-    let cred = auth_data.as_credential();
+    // Save the credential, with what the client told us about the authenticator
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    // An authenticator must create a discoverable credential when one is
+    // required, so credProps may be left out
+    let discoverable = match config.webauthn.registration.resident_key {
+        ResidentKeyRequirement::Required => Some(true),
+        _ => credential.client_extension_results.is_discoverable(),
+    };
+    let cred = auth_data
+        .as_credential()
+        .with_transports(credential.response.transports())
+        .with_authenticator_attachment(credential.authenticator_attachment.clone())
+        .with_user_agent(user_agent)
+        .with_attestation_format(credential.response.attestation()?.fmt)
+        .with_prf_enabled(
+            config.webauthn.rp.prf && credential.client_extension_results.is_prf_enabled(),
+        )?
+        .with_discoverable(discoverable)
+        .with_large_blob_supported(
+            config.webauthn.registration.large_blob != LargeBlobRequirement::None
+                && credential
+                    .client_extension_results
+                    .is_large_blob_supported(),
+        );
+    if config.webauthn.registration.large_blob == LargeBlobRequirement::Required
+        && !cred.large_blob_supported
+    {
+        log::trace!("Credential without large blob support refused by policy");
+        return Ok(
+            HttpResponse::Forbidden().json(r#"{ "message": "Large blob support is required" }"#)
+        );
+    }
     service.add_credential_for_user(&name, &id, &cred).await?;
+    // Only a registration started with the bootstrap token claims the role
+    if session.get("bootstrap").is_some() {
+        service.bootstrap_admin(&name).await?;
+    }
 
+    if config.webauthn.rp.warn_not_backed_up && service.only_credential_not_backed_up(&name).await?
+    {
+        return Ok(HttpResponse::Ok().json(NOT_BACKED_UP_WARNING));
+    }
+    if config.webauthn.registration.resident_key != ResidentKeyRequirement::Discouraged
+        && !service.can_sign_in_without_username(&name).await?
+    {
+        return Ok(HttpResponse::Ok().json(NOT_DISCOVERABLE_WARNING));
+    }
     Ok(HttpResponse::Ok().json(r#"{"status": "ok"}"#))
 }
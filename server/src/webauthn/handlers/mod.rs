@@ -7,3 +7,21 @@ pub mod assertion_challenge;
 pub mod assertion_response;
 pub mod creation_challenge;
 pub mod creation_response;
+
+/// Sent in place of the plain ok status when the policy asks to warn users
+/// whose only credential is not backed up.
+pub const NOT_BACKED_UP_WARNING: &str = concat!(
+    r#"{"status": "ok", "warning": "Your only passkey is not backed up.  "#,
+    r#"Register another one so that you do not lose access."}"#
+);
+
+/// Sent when the policy asks for discoverable credentials, but the user has
+/// none, so they cannot sign in without entering their name.
+pub const NOT_DISCOVERABLE_WARNING: &str = concat!(
+    r#"{"status": "ok", "warning": "Your passkey cannot be used without entering "#,
+    r#"your user name.  Register another one to sign in with just the passkey."}"#
+);
+
+/// Sent when a requested large blob write was not confirmed by the client
+pub const LARGE_BLOB_NOT_WRITTEN_WARNING: &str =
+    r#"{"status": "ok", "warning": "The large blob was not written"}"#;
@@ -0,0 +1,165 @@
+//! Origins accepted in `clientDataJSON`
+//!
+//! A policy lists the origins its clients run on.  An entry is either a web
+//! origin such as `https://www.example.com`, every subdomain of a host as in
+//! `https://*.example.com`, or an Android app identified by the hash of its
+//! signing certificate, `android:apk-key-hash:<base64url sha256>`.
+//!
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use url::Url;
+
+use crate::errors::Error;
+
+pub const ANDROID_APK_KEY_HASH: &str = "android:apk-key-hash:";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub enum AllowedOrigin {
+    /// Exactly this scheme, host and port
+    Web(Url),
+    /// Any subdomain of `host`, but not `host` itself
+    Subdomains {
+        scheme: String,
+        host: String,
+        port: Option<u16>,
+    },
+    /// The full `android:apk-key-hash:` origin of an app
+    AndroidApp(String),
+}
+
+impl AllowedOrigin {
+    /// True if the origin a client reported is allowed by this entry
+    pub fn matches(&self, origin: &Url) -> bool {
+        match self {
+            AllowedOrigin::Web(url) => url.origin() == origin.origin(),
+            AllowedOrigin::Subdomains { scheme, host, port } => {
+                let suffix = format!(".{}", host);
+                origin.scheme() == scheme
+                    && origin.port() == *port
+                    && origin
+                        .host_str()
+                        .is_some_and(|origin_host| origin_host.ends_with(&suffix))
+            }
+            AllowedOrigin::AndroidApp(app) => origin.as_str() == app,
+        }
+    }
+
+    /// The host to check against the RP id, none for apps
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            AllowedOrigin::Web(url) => url.host_str(),
+            AllowedOrigin::Subdomains { host, .. } => Some(host),
+            AllowedOrigin::AndroidApp(_) => None,
+        }
+    }
+}
+
+impl TryFrom<String> for AllowedOrigin {
+    type Error = Error;
+
+    fn try_from(origin: String) -> Result<Self, Self::Error> {
+        let invalid = || Error::InvalidWebauthnPolicy(format!("origin {}", origin));
+        if let Some(hash) = origin.strip_prefix(ANDROID_APK_KEY_HASH) {
+            if hash.is_empty() || base64::decode_config(hash, base64::URL_SAFE_NO_PAD).is_err() {
+                return Err(invalid());
+            }
+            return Ok(AllowedOrigin::AndroidApp(origin));
+        }
+        if let Some((scheme, rest)) = origin.split_once("://*.") {
+            let url = Url::parse(&format!("{}://{}", scheme, rest)).map_err(|_| invalid())?;
+            if url.path() != "/" || url.host_str().is_none() {
+                return Err(invalid());
+            }
+            return Ok(AllowedOrigin::Subdomains {
+                scheme: url.scheme().to_string(),
+                host: url.host_str().unwrap().to_string(),
+                port: url.port(),
+            });
+        }
+        let url = Url::parse(&origin).map_err(|_| invalid())?;
+        if url.path() != "/" || url.host_str().is_none() {
+            return Err(invalid());
+        }
+        Ok(AllowedOrigin::Web(url))
+    }
+}
+
+impl fmt::Display for AllowedOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllowedOrigin::Web(url) => write!(f, "{}", url.origin().ascii_serialization()),
+            AllowedOrigin::Subdomains { scheme, host, port } => match port {
+                Some(port) => write!(f, "{}://*.{}:{}", scheme, host, port),
+                None => write!(f, "{}://*.{}", scheme, host),
+            },
+            AllowedOrigin::AndroidApp(app) => write!(f, "{}", app),
+        }
+    }
+}
+
+impl From<AllowedOrigin> for String {
+    fn from(origin: AllowedOrigin) -> Self {
+        origin.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(origin: &str) -> AllowedOrigin {
+        AllowedOrigin::try_from(origin.to_string()).expect("oops")
+    }
+
+    fn url(origin: &str) -> Url {
+        Url::parse(origin).expect("oops")
+    }
+
+    #[test]
+    fn test_web_origin() {
+        let origin = allowed("https://www.example.com");
+        assert!(origin.matches(&url("https://www.example.com")));
+        assert!(origin.matches(&url("https://www.example.com:443")));
+        assert!(!origin.matches(&url("http://www.example.com")));
+        assert!(!origin.matches(&url("https://www.example.com:8443")));
+        assert!(!origin.matches(&url("https://app.example.com")));
+        assert_eq!(origin.to_string(), "https://www.example.com");
+
+        assert!(AllowedOrigin::try_from("https://example.com/login".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_subdomains() {
+        let origin = allowed("https://*.example.com");
+        assert!(origin.matches(&url("https://app.example.com")));
+        assert!(origin.matches(&url("https://a.b.example.com")));
+        assert!(!origin.matches(&url("https://example.com")));
+        assert!(!origin.matches(&url("https://badexample.com")));
+        assert!(!origin.matches(&url("http://app.example.com")));
+        assert_eq!(origin.host(), Some("example.com"));
+        assert_eq!(origin.to_string(), "https://*.example.com");
+
+        let origin = allowed("http://*.localhost:3000");
+        assert!(origin.matches(&url("http://app.localhost:3000")));
+        assert!(!origin.matches(&url("http://app.localhost:3001")));
+    }
+
+    #[test]
+    fn test_android_app() {
+        let app = "android:apk-key-hash:47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU";
+        let origin = allowed(app);
+        assert!(origin.matches(&url(app)));
+        assert!(!origin.matches(&url("android:apk-key-hash:AAAA")));
+        assert_eq!(origin.host(), None);
+
+        assert!(AllowedOrigin::try_from("android:apk-key-hash:".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let json = r#"["https://example.com","https://*.example.com"]"#;
+        let origins: Vec<AllowedOrigin> = serde_json::from_str(json).expect("oops");
+        assert_eq!(serde_json::to_string(&origins).expect("oops"), json);
+    }
+}
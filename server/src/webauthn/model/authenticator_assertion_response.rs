@@ -34,16 +34,43 @@ impl AuthenticatorAssertionResponse {
             .map_err(Error::ClientDataParseError)
     }
 
+    /// Whether the authenticator verified the user.  Only meaningful once the
+    /// response has been verified.
+    pub fn is_user_verified(&self) -> Result<bool, Error> {
+        let auth_data = AuthenticatorData::try_from(self.authenticator_data.as_ref())?;
+        Ok(auth_data.is_user_verified())
+    }
+
     /// The challenge should be provided from the session.
     /// The origin is the RP url, such as "http://localhost:3000"
     /// Verify returns the updated credential, so that counters can be tracked appropriately.
+    /// The client extension results say whether the legacy AppID was used.
     pub fn verify(
         &self,
         policy: &WebauthnPolicy,
         challenge: &Base64UrlSafeData,
         credential: &Credential,
+        client_extension_results: &AuthenticationExtensionsClientOutputs,
     ) -> Result<Credential, Error> {
-        self.verify_packed(policy, challenge, credential)
+        self.verify_packed(policy, challenge, credential, client_extension_results)
+    }
+
+    /// The rpIdHash the authenticator data must have.  A client that used the
+    /// `appid` extension reports `appid: true`, and the authenticator then
+    /// scoped the credential to the U2F AppID instead of the RP id.
+    fn expected_rp_id_hash(
+        policy: &WebauthnPolicy,
+        client_extension_results: &AuthenticationExtensionsClientOutputs,
+    ) -> Result<[u8; 32], Error> {
+        if client_extension_results.appid != Some(true) {
+            return Ok(sha256(policy.rp.id.as_bytes()));
+        }
+        match &policy.rp.app_id {
+            Some(app_id) => Ok(sha256(app_id.as_bytes())),
+            None => Err(Error::AssertionVerificationError(
+                "appid was used, but no AppID is configured".to_string(),
+            )),
+        }
     }
 
     /// Verify the response provided in packed format.
@@ -52,6 +79,7 @@ impl AuthenticatorAssertionResponse {
         policy: &WebauthnPolicy,
         challenge: &Base64UrlSafeData,
         credential: &Credential,
+        client_extension_results: &AuthenticationExtensionsClientOutputs,
     ) -> Result<Credential, Error> {
         log::trace!("Verify start");
         let client_data = self.get_client_data()?;
@@ -72,10 +100,8 @@ impl AuthenticatorAssertionResponse {
             return Err(Error::BadChallenge);
         }
 
-        // 7.2 step 13; Verify the origin
-        if client_data.origin != policy.origin {
-            return Err(Error::BadOrigin);
-        }
+        // 7.2 step 13; Verify the origin, and the top origin when cross-origin
+        policy.rp.verify_origin(&client_data)?;
 
         // 7.2 step 14; Verify token binding
         if let Some(token_binding) = client_data.token_binding {
@@ -85,7 +111,7 @@ impl AuthenticatorAssertionResponse {
         // 7.2 step 15: Verify the rp_id hash
         // If no RP ID is sent by the RP, then the origin domain is used.
         // ( just the domain.  No scheme or port)
-        let rp_id_hash = sha256(policy.rp_id.as_bytes());
+        let rp_id_hash = Self::expected_rp_id_hash(policy, client_extension_results)?;
         let auth_data =
             AuthenticatorData::try_from(self.authenticator_data.as_ref()).map_err(|e| {
                 log::trace!("Failed to decode AuthenticatorData");
@@ -97,18 +123,15 @@ impl AuthenticatorAssertionResponse {
             ));
         }
 
-        // 7.2 step 16; Verify userPresent flag
-        if !auth_data.is_user_present() {
-            log::trace!("Verify: User not present");
-            return Err(Error::AssertionVerificationError(
-                "userPresent flag not set".to_string(),
-            ));
-        }
-        // 7.2 step 17; Verify userPresent flag
-        if !auth_data.is_user_verified() {
-            log::trace!("Verify: User not verified");
+        // 7.2 steps 16 and 17; Verify the userPresent and userVerified flags
+        auth_data.verify_user_flags(&policy.authentication.user_verification)?;
+
+        // 7.2 step 18: BE is fixed when the credential is created.  BS may
+        // change, so record the current value.
+        if auth_data.is_backup_eligible() != credential.is_backup_eligible() {
+            log::trace!("Verify: Backup eligibility changed");
             return Err(Error::AssertionVerificationError(
-                "userVerified flag not set".to_string(),
+                "backupEligible flag changed".to_string(),
             ));
         }
 
@@ -117,7 +140,8 @@ impl AuthenticatorAssertionResponse {
         // this should be a matter of policy.
         let mut new_cred = credential.clone();
         new_cred.last = Utc::now();
-        if policy.validate_sign_count && auth_data.counter <= credential.counter {
+        new_cred.backup_state = auth_data.is_backed_up();
+        if policy.authentication.validate_sign_count && auth_data.counter <= credential.counter {
             log::trace!("ERROR!!!  Bad signCount {:}", &auth_data.counter);
             return Err(Error::BadSignCounter);
         } else {
@@ -171,4 +195,22 @@ mod tests {
         dbg!(&pk_cred);
         Ok(())
     }
+
+    #[test]
+    fn test_expected_rp_id_hash() -> Result<(), Error> {
+        let mut policy = crate::config::AppConfig::default().webauthn;
+        policy.rp.app_id = None;
+
+        let mut outputs = AuthenticationExtensionsClientOutputs::default();
+        let hash = AuthenticatorAssertionResponse::expected_rp_id_hash(&policy, &outputs)?;
+        assert_eq!(hash, sha256(policy.rp.id.as_bytes()));
+
+        outputs.appid = Some(true);
+        assert!(AuthenticatorAssertionResponse::expected_rp_id_hash(&policy, &outputs).is_err());
+
+        policy.rp.app_id = Some("https://example.com/appid.json".to_string());
+        let hash = AuthenticatorAssertionResponse::expected_rp_id_hash(&policy, &outputs)?;
+        assert_eq!(hash, sha256(b"https://example.com/appid.json"));
+        Ok(())
+    }
 }
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use super::AuthenticatorTransport;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum AuthenticatorAttachment {
@@ -9,3 +11,18 @@ pub enum AuthenticatorAttachment {
     #[serde(rename = "multi-platform")]
     MultiPlatform,
 }
+
+impl AuthenticatorAttachment {
+    /// True if an authenticator with this attachment can be reached over
+    /// `transport`.  Platform authenticators are internal, and anything else
+    /// is a roaming authenticator.
+    pub fn allows_transport(&self, transport: &AuthenticatorTransport) -> bool {
+        match self {
+            AuthenticatorAttachment::Platform => *transport == AuthenticatorTransport::Internal,
+            AuthenticatorAttachment::CrossPlatform => {
+                *transport != AuthenticatorTransport::Internal
+            }
+            AuthenticatorAttachment::MultiPlatform => true,
+        }
+    }
+}
@@ -23,6 +23,10 @@ pub struct AuthenticatorAttestationResponse {
     pub attestation_object: Base64UrlSafeData,
     #[serde(rename = "clientDataJSON")]
     pub client_data_json: Base64UrlSafeData,
+    /// The result of `getTransports()`, added by the client since the method
+    /// itself does not survive serialization.
+    #[serde(default)]
+    pub transports: Option<Vec<String>>,
     // Bogus baggage
     pub get_authenticator_data: Option<GetAuthenticatorData>,
     pub get_public_key: Option<GetPublicKey>,
@@ -36,6 +40,13 @@ impl AuthenticatorAttestationResponse {
             .map_err(Error::ClientDataParseError)
     }
 
+    /// The transports the authenticator reported, ignoring unknown values.
+    pub fn transports(&self) -> Option<Vec<AuthenticatorTransport>> {
+        self.transports
+            .as_deref()
+            .map(AuthenticatorTransport::parse_all)
+    }
+
     /// Throws an error if no attStmt was provided
     pub fn attestation(&self) -> Result<Attestation, Error> {
         Attestation::try_from(&self.attestation_object)
@@ -77,10 +88,8 @@ impl AuthenticatorAttestationResponse {
 
         log::trace!("Verify: challenge matched");
 
-        // Verify the origin
-        if client_data.origin != policy.origin {
-            return Err(Error::BadOrigin);
-        }
+        // Verify the origin, and the top origin when cross-origin
+        policy.rp.verify_origin(&client_data)?;
         log::trace!("Verify: origin matched");
 
         // 7.1 step 7: Verify the type
@@ -102,7 +111,7 @@ impl AuthenticatorAttestationResponse {
         // If no RP ID is sent by the RP, then the origin domain is used.
         // ( just the domain.  No scheme or port)
 
-        let rp_id_hash = sha256(policy.rp_id.as_bytes());
+        let rp_id_hash = sha256(policy.rp.id.as_bytes());
         if rp_id_hash != attestation.auth_data.rp_id_hash {
             return Err(Error::AssertionVerificationError(
                 "RP ID Hash does not match".to_string(),
@@ -110,6 +119,11 @@ impl AuthenticatorAttestationResponse {
         }
         log::trace!("Verify: rp_id_hash matched");
 
+        // 7.1 steps 14 and 15: Verify the userPresent and userVerified flags
+        attestation
+            .auth_data
+            .verify_user_flags(&policy.registration.user_verification)?;
+
         //------------- Verify the signature --------------
 
         // 7.1 Step 11: Perform a sha256 hash of the client data
@@ -155,10 +169,8 @@ impl AuthenticatorAttestationResponse {
 
         log::trace!("Verify: challenge matched");
 
-        // Verify the origin
-        if client_data.origin != policy.origin {
-            return Err(Error::BadOrigin);
-        }
+        // Verify the origin, and the top origin when cross-origin
+        policy.rp.verify_origin(&client_data)?;
         log::trace!("Verify: origin matched");
 
         // 7.1 step 7: Verify the type
@@ -176,7 +188,7 @@ impl AuthenticatorAttestationResponse {
         // If no RP ID is sent by the RP, then the origin domain is used.
         // ( just the domain.  No scheme or port)
 
-        let rp_id_hash = sha256(policy.rp_id.as_bytes());
+        let rp_id_hash = sha256(policy.rp.id.as_bytes());
         if rp_id_hash != attestation.auth_data.rp_id_hash {
             return Err(Error::AssertionVerificationError(
                 "RP ID Hash does not match".to_string(),
@@ -184,6 +196,11 @@ impl AuthenticatorAttestationResponse {
         }
         log::trace!("Verify: rp_id_hash matched");
 
+        // 7.1 steps 14 and 15: Verify the userPresent and userVerified flags
+        attestation
+            .auth_data
+            .verify_user_flags(&policy.registration.user_verification)?;
+
         // There is no signature in a Passkey
         log::trace!("Verify: passkey verification complete");
         Ok(attestation.auth_data.clone())
@@ -13,7 +13,7 @@ use super::*;
 /// - AAGUID: 16
 /// - LENGTH: 2
 /// - CREDENTIAL ID: LENGTH
-/// - CREDENTIAL PUBLIC KEY: (remaining) COSE_Key
+/// - CREDENTIAL PUBLIC KEY: COSE_Key
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CredentialData {
@@ -34,46 +34,46 @@ impl CredentialData {
 impl TryFrom<&[u8]> for CredentialData {
     type Error = Error;
     fn try_from(data: &[u8]) -> Result<Self, Error> {
-        log::trace!("CredentialData::try_from start");
-        let data_len: usize = data.len();
-        let mut remainder = data_len;
+        Ok(Self::parse(data)?.0)
+    }
+}
+
+impl CredentialData {
+    /// Parse the attested credential data at the front of `data`.  Extension
+    /// outputs may follow the COSE key, so this also returns the number of
+    /// bytes that were used.
+    pub fn parse(data: &[u8]) -> Result<(Self, usize), Error> {
+        log::trace!("CredentialData::parse start");
         let mut file = Cursor::new(data);
 
         let mut aaguid: [u8; 16] = [0; 16];
-        let bytes_read = file
-            .read(&mut aaguid)
+        file.read_exact(&mut aaguid)
             .map_err(|_| Error::AuthenticatorDataDeserialize("AAGUID".to_string()))?;
-        remainder -= bytes_read;
 
         let mut length: [u8; 2] = [0; 2];
-        let bytes_read = file
-            .read(&mut length)
+        file.read_exact(&mut length)
             .map_err(|_| Error::AuthenticatorDataDeserialize("LENGTH".to_string()))?;
-        remainder -= bytes_read;
 
         let length = u16::from_be_bytes(length);
 
         let mut credential_id: Vec<u8> = vec![0; length as usize];
-        let bytes_read = file
-            .read(&mut credential_id)
+        file.read_exact(&mut credential_id)
             .map_err(|_| Error::AuthenticatorDataDeserialize("CREDENTIAL ID".to_string()))?;
 
-        remainder -= bytes_read;
-
-        let mut credential_public_key_bytes: Vec<u8> = vec![0; remainder];
-        let _ = file
-            .read(&mut credential_public_key_bytes)
-            .map_err(|_| Error::AuthenticatorDataDeserialize("COSE PUBLICKEY".to_string()))?;
-        let credential_public_key =
-            CoseKey::decode_bytes(&credential_public_key_bytes).map_err(Error::CoseKeyError)?;
-
-        log::trace!("CredentialData::try_from succeeded");
-        Ok(Self {
-            aaguid,
-            credential_id,
-            credential_public_key,
-            extensions: None,
-        })
+        let key_start = file.position() as usize;
+        let (credential_public_key, key_len) =
+            CoseKey::decode_prefix(&data[key_start..]).map_err(Error::CoseKeyError)?;
+
+        log::trace!("CredentialData::parse succeeded");
+        Ok((
+            Self {
+                aaguid,
+                credential_id,
+                credential_public_key,
+                extensions: None,
+            },
+            key_start + key_len,
+        ))
     }
 }
 
@@ -81,17 +81,21 @@ impl TryFrom<&[u8]> for CredentialData {
 /// - RP ID hash: 32
 /// - FLAGS: 1
 /// - COUNTER: 4 (big endian)
-/// - attestedCredentialData
+/// - attestedCredentialData (if AT is set)
+/// - extensions: CBOR map (if ED is set)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AuthenticatorData {
     pub rp_id_hash: [u8; 32],
     pub flags: u8,
     pub counter: u32,
     pub credential_data: Option<CredentialData>,
+    pub extensions: Option<AuthenticatorExtensionOutputs>,
 }
 
 pub const USER_PRESENT: u8 = 1;
 pub const USER_VERIFIED: u8 = 4;
+pub const BACKUP_ELIGIBLE: u8 = 8;
+pub const BACKUP_STATE: u8 = 16;
 pub const ATTESTED_CREDENTIAL_DATA_INCLUDED: u8 = 64;
 pub const EXTENSION_DATA_INCLUDED: u8 = 128;
 
@@ -125,34 +129,60 @@ impl TryFrom<&[u8]> for AuthenticatorData {
             .map_err(|_| Error::AuthenticatorDataDeserialize("FLAGS".to_string()))?;
         let flags = flags[0];
 
+        // A credential cannot be backed up unless it is backup eligible
+        if (flags & BACKUP_STATE) != 0 && (flags & BACKUP_ELIGIBLE) == 0 {
+            return Err(Error::AuthenticatorDataDeserialize(
+                "FLAGS: BS set without BE".to_string(),
+            ));
+        }
+
         let mut counter: [u8; 4] = [0; 4];
         let _ = file
             .read(&mut counter)
             .map_err(|_| Error::AuthenticatorDataDeserialize("COUNTER".to_string()))?;
         let counter = u32::from_be_bytes(counter);
 
+        if data_len < front_matter_len {
+            return Err(Error::AuthenticatorDataDeserialize("COUNTER".to_string()));
+        }
+        let mut offset = front_matter_len;
+
         // If attested credential data was included, unpack it
-        let credential_data = match (flags & ATTESTED_CREDENTIAL_DATA_INCLUDED) != 0 {
+        let mut credential_data = match (flags & ATTESTED_CREDENTIAL_DATA_INCLUDED) != 0 {
             true => {
-                let remainder = data_len - front_matter_len;
-                let mut bytes: Vec<u8> = vec![0; remainder];
-                let bytes_read = file.read(&mut bytes).map_err(|_| {
-                    Error::AuthenticatorDataDeserialize("Credentialdata".to_string())
-                })?;
-                if remainder != bytes_read {
-                    log::trace!("Oops!! Too few bytes read!");
-                }
-                Some(CredentialData::try_from(bytes.as_ref())?)
+                let (credential_data, used) = CredentialData::parse(&data[offset..])?;
+                offset += used;
+                Some(credential_data)
             }
             false => None,
         };
 
+        // Extension outputs, if any, are the remainder
+        let extensions = match (flags & EXTENSION_DATA_INCLUDED) != 0 {
+            true => {
+                let bytes = &data[offset..];
+                if let Some(credential_data) = credential_data.as_mut() {
+                    credential_data.extensions = Some(Base64UrlSafeData(bytes.to_vec()));
+                }
+                Some(AuthenticatorExtensionOutputs::try_from(bytes)?)
+            }
+            false => {
+                if offset != data_len {
+                    return Err(Error::AuthenticatorDataDeserialize(
+                        "Unexpected trailing bytes".to_string(),
+                    ));
+                }
+                None
+            }
+        };
+
         log::trace!("AuthenticatorData::try_from succeeded");
         Ok(Self {
             rp_id_hash,
             flags,
             counter,
             credential_data,
+            extensions,
         })
     }
 }
@@ -192,6 +222,32 @@ impl AuthenticatorData {
         self.test_flag(USER_VERIFIED)
     }
 
+    /// Check the UP and UV flags for a ceremony.  UP is always required, UV
+    /// only if the policy requires it.  Returns whether the user was verified.
+    pub fn verify_user_flags(
+        &self,
+        user_verification: &UserVerificationRequirement,
+    ) -> Result<bool, Error> {
+        if !self.is_user_present() {
+            log::trace!("Verify: User not present");
+            return Err(Error::UserNotPresent);
+        }
+        let user_verified = self.is_user_verified();
+        if *user_verification == UserVerificationRequirement::Required && !user_verified {
+            log::trace!("Verify: User not verified");
+            return Err(Error::UserNotVerified);
+        }
+        Ok(user_verified)
+    }
+
+    pub fn is_backup_eligible(&self) -> bool {
+        self.test_flag(BACKUP_ELIGIBLE)
+    }
+
+    pub fn is_backed_up(&self) -> bool {
+        self.test_flag(BACKUP_STATE)
+    }
+
     pub fn is_attested_credential_data_included(&self) -> bool {
         self.test_flag(ATTESTED_CREDENTIAL_DATA_INCLUDED)
     }
@@ -201,6 +257,7 @@ impl AuthenticatorData {
 
     pub fn as_credential(&self) -> Credential {
         let credential_data = self.credential_data().expect("damnit");
+        let now = Utc::now();
         Credential {
             id: Base64UrlSafeData(credential_data.credential_id.clone()),
             type_: PublicKeyCredentialType::PublicKey,
@@ -208,7 +265,21 @@ impl AuthenticatorData {
             aaguid: credential_data.aaguid,
             credential_public_key: credential_data.credential_public_key,
             flags: self.flags,
-            last: Utc::now(),
+            last: now,
+            created: Some(now),
+            transports: None,
+            authenticator_attachment: None,
+            user_agent: None,
+            backup_eligible: self.is_backup_eligible(),
+            backup_state: self.is_backed_up(),
+            attestation_format: None,
+            prf_enabled: false,
+            prf_salt: None,
+            prf_next_salt: None,
+            large_blob_supported: false,
+            cred_protect: self.extensions.as_ref().and_then(|e| e.cred_protect()),
+            min_pin_length: self.extensions.as_ref().and_then(|e| e.min_pin_length()),
+            discoverable: None,
         }
     }
 }
@@ -252,4 +323,44 @@ mod tests {
         );
         dbg!(&pub_key);
     }
+
+    #[test]
+    fn test_backup_flags() {
+        let mut data: Vec<u8> = vec![0; 37];
+        data[32] = USER_PRESENT | BACKUP_ELIGIBLE | BACKUP_STATE;
+        let auth_data = AuthenticatorData::try_from(data.as_slice()).expect("oops");
+        assert!(auth_data.is_backup_eligible());
+        assert!(auth_data.is_backed_up());
+
+        data[32] = USER_PRESENT | BACKUP_ELIGIBLE;
+        let auth_data = AuthenticatorData::try_from(data.as_slice()).expect("oops");
+        assert!(auth_data.is_backup_eligible());
+        assert!(!auth_data.is_backed_up());
+
+        data[32] = USER_PRESENT | BACKUP_STATE;
+        assert!(AuthenticatorData::try_from(data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_verify_user_flags() {
+        let required = UserVerificationRequirement::Required;
+        let discouraged = UserVerificationRequirement::Discouraged;
+        let mut data: Vec<u8> = vec![0; 37];
+
+        data[32] = USER_PRESENT | USER_VERIFIED;
+        let auth_data = AuthenticatorData::try_from(data.as_slice()).expect("oops");
+        assert!(auth_data.verify_user_flags(&required).expect("oops"));
+
+        data[32] = USER_PRESENT;
+        let auth_data = AuthenticatorData::try_from(data.as_slice()).expect("oops");
+        let result = auth_data.verify_user_flags(&required);
+        assert!(matches!(result, Err(Error::UserNotVerified)));
+        assert!(!auth_data.verify_user_flags(&discouraged).expect("oops"));
+
+        // UP is required whatever the policy says about UV
+        data[32] = USER_VERIFIED;
+        let auth_data = AuthenticatorData::try_from(data.as_slice()).expect("oops");
+        let result = auth_data.verify_user_flags(&discouraged);
+        assert!(matches!(result, Err(Error::UserNotPresent)));
+    }
 }
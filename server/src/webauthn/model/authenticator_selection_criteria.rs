@@ -30,10 +30,10 @@ impl AuthenticatorSelectionCriteria {
 impl From<&WebauthnPolicy> for AuthenticatorSelectionCriteria {
     fn from(policy: &WebauthnPolicy) -> Self {
         Self {
-            authenticator_attachment: Some(policy.authenticator_attachment.clone()),
-            resident_key: Some(policy.resident_key.clone()),
+            authenticator_attachment: Some(policy.registration.authenticator_attachment.clone()),
+            resident_key: Some(policy.registration.resident_key.clone()),
             require_resident_key: None,
-            user_verification: Some(policy.user_verification.clone()),
+            user_verification: Some(policy.registration.user_verification.clone()),
         }
     }
 }
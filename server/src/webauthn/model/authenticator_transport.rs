@@ -4,10 +4,48 @@ use serde::{Deserialize, Serialize};
 pub enum AuthenticatorTransport {
     #[serde(rename = "usb")]
     USB,
-    #[serde(rename = "NFC")]
+    #[serde(rename = "nfc", alias = "NFC")]
     NFC,
     #[serde(rename = "ble")]
     BLE,
+    #[serde(rename = "smart-card")]
+    SmartCard,
+    #[serde(rename = "hybrid")]
+    Hybrid,
     #[serde(rename = "internal")]
     Internal,
 }
+
+impl AuthenticatorTransport {
+    /// Parse the values reported by `getTransports()`.  Clients must ignore
+    /// transports they do not recognise, so unknown values are dropped.
+    pub fn parse_all(values: &[String]) -> Vec<AuthenticatorTransport> {
+        values
+            .iter()
+            .filter_map(|value| {
+                serde_json::from_value(serde_json::Value::String(value.clone())).ok()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_all() {
+        let values: Vec<String> = ["internal", "hybrid", "nfc", "carrier-pigeon"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            AuthenticatorTransport::parse_all(&values),
+            vec![
+                AuthenticatorTransport::Internal,
+                AuthenticatorTransport::Hybrid,
+                AuthenticatorTransport::NFC
+            ]
+        );
+    }
+}
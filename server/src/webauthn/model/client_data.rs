@@ -9,6 +9,11 @@ use url::Url;
     "origin":"http://localhost:3000",
     "crossOrigin":false
 }
+
+In an iframe on another site, crossOrigin is true and topOrigin is the
+origin of the top level page:
+    "crossOrigin":true,
+    "topOrigin":"https://shop.example.org"
 */
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
@@ -40,6 +45,7 @@ pub struct ClientData {
     pub challenge: Base64UrlSafeData,
     pub origin: Url,
     pub cross_origin: Option<bool>,
+    pub top_origin: Option<Url>,
     pub token_binding: Option<TokenBinding>,
 }
 
@@ -60,4 +66,21 @@ mod tests {
         let result: ClientData = serde_json::from_str(json).expect("not yet");
         dbg!(&result);
     }
+
+    #[test]
+    fn test_top_origin() {
+        let json = r#"{
+            "type":"webauthn.get",
+            "challenge":"6P8pAZg6ARXv5SsttAet8XN0IJ1wyF7lZegY-tPq3BgA",
+            "origin":"https://pay.example.com",
+            "crossOrigin":true,
+            "topOrigin":"https://shop.example.org"
+        }
+        "#;
+
+        let result: ClientData = serde_json::from_str(json).expect("oops");
+        assert_eq!(result.cross_origin, Some(true));
+        let top_origin = result.top_origin.expect("oops");
+        assert_eq!(top_origin.host_str(), Some("shop.example.org"));
+    }
 }
@@ -4,9 +4,12 @@ use serde_repr::{Deserialize_repr, Serialize_repr};
 #[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq, Eq, Clone, Copy)]
 #[allow(non_camel_case_types)]
 pub enum COSEAlgorithm {
-    RSS512 = -259,
-    RSS384 = -258,
-    RSS256 = -257,
+    RS512 = -259,
+    RS384 = -258,
+    RS256 = -257,
+    ML_DSA_87 = -50,
+    ML_DSA_65 = -49,
+    ML_DSA_44 = -48,
     ES256K = -47,
     HSS_LMS = -46,
     SHA512 = 44,
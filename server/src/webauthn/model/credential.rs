@@ -1,10 +1,15 @@
 //! Model for stored credential
+//!
+//! The registration metadata was added after credentials were first stored,
+//! so those fields default when reading older documents.
 use base64urlsafedata::Base64UrlSafeData;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use super::*;
-use crate::cose::keys::CoseKey;
+use crate::{cose::keys::CoseKey, errors::Error, utils::make_id};
+
+pub const PRF_SALT_LEN: usize = 32;
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct Credential {
@@ -16,6 +21,46 @@ pub struct Credential {
     pub credential_public_key: CoseKey,
     pub flags: u8,
     pub last: DateTime<Utc>,
+    /// The time the credential was registered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+    /// As reported by `getTransports()`, used as a hint in credential descriptors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transports: Option<Vec<AuthenticatorTransport>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authenticator_attachment: Option<AuthenticatorAttachment>,
+    /// The User-Agent of the browser that registered the credential
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// The credential may be synced to other devices (BE flag)
+    #[serde(default)]
+    pub backup_eligible: bool,
+    /// The credential is currently backed up (BS flag)
+    #[serde(default)]
+    pub backup_state: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation_format: Option<AttestationFormatIdentifier>,
+    /// The authenticator supports the `prf` extension for this credential
+    #[serde(default)]
+    pub prf_enabled: bool,
+    /// The salt the client evaluates the PRF with to derive its key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prf_salt: Option<Base64UrlSafeData>,
+    /// The salt that replaces `prf_salt` once a rotation is committed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prf_next_salt: Option<Base64UrlSafeData>,
+    /// The credential can store a large blob on the authenticator
+    #[serde(default)]
+    pub large_blob_supported: bool,
+    /// The `credProtect` level reported at registration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cred_protect: Option<CredentialProtectionPolicy>,
+    /// The minimum PIN length reported at registration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_pin_length: Option<u32>,
+    /// The `rk` credential property.  None if the client did not report it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discoverable: Option<bool>,
 }
 
 impl Credential {
@@ -25,4 +70,54 @@ impl Credential {
         new.last = Utc::now();
         new
     }
+
+    /// Credentials stored before `backup_eligible` was recorded still have
+    /// the BE flag in the registration `flags`.
+    pub fn is_backup_eligible(&self) -> bool {
+        self.backup_eligible || (self.flags & BACKUP_ELIGIBLE) != 0
+    }
+
+    pub fn with_transports(mut self, transports: Option<Vec<AuthenticatorTransport>>) -> Self {
+        self.transports = transports;
+        self
+    }
+
+    pub fn with_authenticator_attachment(
+        mut self,
+        authenticator_attachment: Option<AuthenticatorAttachment>,
+    ) -> Self {
+        self.authenticator_attachment = authenticator_attachment;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: Option<String>) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+
+    /// Record PRF support.  A credential with PRF enabled is given a random salt.
+    pub fn with_prf_enabled(mut self, prf_enabled: bool) -> Result<Self, Error> {
+        self.prf_enabled = prf_enabled;
+        self.prf_salt = match prf_enabled {
+            true => Some(Base64UrlSafeData(make_id(PRF_SALT_LEN)?)),
+            false => None,
+        };
+        self.prf_next_salt = None;
+        Ok(self)
+    }
+
+    pub fn with_discoverable(mut self, discoverable: Option<bool>) -> Self {
+        self.discoverable = discoverable;
+        self
+    }
+
+    pub fn with_large_blob_supported(mut self, large_blob_supported: bool) -> Self {
+        self.large_blob_supported = large_blob_supported;
+        self
+    }
+
+    pub fn with_attestation_format(mut self, fmt: AttestationFormatIdentifier) -> Self {
+        self.attestation_format = Some(fmt);
+        self
+    }
 }
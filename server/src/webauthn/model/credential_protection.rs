@@ -0,0 +1,167 @@
+//! The `credProtect` and `minPinLength` extensions
+//!
+//! Both are requested at registration and answered in the authenticator
+//! extension outputs, so they are checked against the policy there.
+//!
+use serde::{Deserialize, Serialize};
+
+use super::{AuthenticatorExtensionOutputs, RegistrationPolicy};
+use crate::errors::Error;
+
+/// The `credentialProtectionPolicy` input, from least to most protective.
+/// `UserVerificationOptional` is what authenticators do without the extension.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum CredentialProtectionPolicy {
+    #[default]
+    UserVerificationOptional,
+    #[serde(rename = "userVerificationOptionalWithCredentialIDList")]
+    UserVerificationOptionalWithCredentialIdList,
+    UserVerificationRequired,
+}
+
+impl CredentialProtectionPolicy {
+    /// The `credProtect` value in the authenticator data
+    pub fn level(&self) -> i128 {
+        match self {
+            CredentialProtectionPolicy::UserVerificationOptional => 1,
+            CredentialProtectionPolicy::UserVerificationOptionalWithCredentialIdList => 2,
+            CredentialProtectionPolicy::UserVerificationRequired => 3,
+        }
+    }
+
+    pub fn from_level(level: i128) -> Option<Self> {
+        match level {
+            1 => Some(CredentialProtectionPolicy::UserVerificationOptional),
+            2 => Some(CredentialProtectionPolicy::UserVerificationOptionalWithCredentialIdList),
+            3 => Some(CredentialProtectionPolicy::UserVerificationRequired),
+            _ => None,
+        }
+    }
+}
+
+impl AuthenticatorExtensionOutputs {
+    pub fn cred_protect(&self) -> Option<CredentialProtectionPolicy> {
+        self.get_integer("credProtect")
+            .and_then(CredentialProtectionPolicy::from_level)
+    }
+
+    /// Only reported if the authenticator is configured to share it with the RP
+    pub fn min_pin_length(&self) -> Option<u32> {
+        self.get_integer("minPinLength")
+            .and_then(|len| u32::try_from(len).ok())
+    }
+
+    /// Check a new credential against the policy's `credProtect` level and
+    /// minimum PIN length.  A PIN length that was not reported is not met.
+    pub fn verify_credential_protection(&self, policy: &RegistrationPolicy) -> Result<(), Error> {
+        let cred_protect = self.cred_protect().unwrap_or_default();
+        if cred_protect < policy.cred_protect {
+            return Err(Error::CredentialProtectionError(format!(
+                "credProtect level {} is below {}",
+                cred_protect.level(),
+                policy.cred_protect.level()
+            )));
+        }
+        if policy.min_pin_length > 0 {
+            match self.min_pin_length() {
+                Some(len) if len >= policy.min_pin_length => (),
+                Some(len) => {
+                    return Err(Error::CredentialProtectionError(format!(
+                        "minPinLength {} is below {}",
+                        len, policy.min_pin_length
+                    )))
+                }
+                None => {
+                    return Err(Error::CredentialProtectionError(
+                        "minPinLength was not reported".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_cbor::Value;
+
+    fn outputs(entries: &[(&str, i128)]) -> AuthenticatorExtensionOutputs {
+        AuthenticatorExtensionOutputs(
+            entries
+                .iter()
+                .map(|(name, value)| (name.to_string(), Value::Integer(*value)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_levels() {
+        let policy = CredentialProtectionPolicy::UserVerificationOptionalWithCredentialIdList;
+        assert_eq!(
+            CredentialProtectionPolicy::from_level(policy.level()),
+            Some(policy)
+        );
+        assert_eq!(CredentialProtectionPolicy::from_level(4), None);
+        assert_eq!(
+            serde_json::to_string(&policy).expect("oops"),
+            r#""userVerificationOptionalWithCredentialIDList""#
+        );
+        assert_eq!(
+            outputs(&[("credProtect", 3)]).cred_protect(),
+            Some(CredentialProtectionPolicy::UserVerificationRequired)
+        );
+    }
+
+    fn policy(cred_protect: CredentialProtectionPolicy, min_pin_length: u32) -> RegistrationPolicy {
+        let mut policy = crate::config::AppConfig::default().webauthn.registration;
+        policy.cred_protect = cred_protect;
+        policy.min_pin_length = min_pin_length;
+        policy
+    }
+
+    #[test]
+    fn test_verify_cred_protect() {
+        let required = policy(CredentialProtectionPolicy::UserVerificationRequired, 0);
+        let result = outputs(&[("credProtect", 2)]).verify_credential_protection(&required);
+        assert!(matches!(result, Err(Error::CredentialProtectionError(_))));
+        // An authenticator that ignored the extension has the lowest level
+        let result = outputs(&[]).verify_credential_protection(&required);
+        assert!(matches!(result, Err(Error::CredentialProtectionError(_))));
+        assert!(outputs(&[("credProtect", 3)])
+            .verify_credential_protection(&required)
+            .is_ok());
+
+        let list = policy(
+            CredentialProtectionPolicy::UserVerificationOptionalWithCredentialIdList,
+            0,
+        );
+        assert!(outputs(&[("credProtect", 2)])
+            .verify_credential_protection(&list)
+            .is_ok());
+        assert!(outputs(&[("credProtect", 3)])
+            .verify_credential_protection(&list)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_verify_min_pin_length() {
+        let policy = policy(CredentialProtectionPolicy::UserVerificationOptional, 6);
+        let result = outputs(&[("minPinLength", 4)]).verify_credential_protection(&policy);
+        assert!(matches!(result, Err(Error::CredentialProtectionError(_))));
+        let result = outputs(&[]).verify_credential_protection(&policy);
+        assert!(matches!(result, Err(Error::CredentialProtectionError(_))));
+        assert!(outputs(&[("minPinLength", 6)])
+            .verify_credential_protection(&policy)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_min_pin_length() {
+        assert_eq!(outputs(&[("minPinLength", 8)]).min_pin_length(), Some(8));
+        assert_eq!(outputs(&[("minPinLength", -1)]).min_pin_length(), None);
+        assert_eq!(outputs(&[]).min_pin_length(), None);
+    }
+}
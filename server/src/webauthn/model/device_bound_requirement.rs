@@ -0,0 +1,20 @@
+//! Require device-bound credentials
+//!
+//! A credential whose authenticator reports the backup eligible (BE) flag may
+//! be synced to other devices, e.g. a passkey in a platform keychain.  Some
+//! deployments want the stronger guarantee of a key that never leaves the
+//! authenticator.
+//!
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceBoundRequirement {
+    /// Synced credentials are accepted
+    #[default]
+    None,
+    /// Only the admin API requires a session from a device-bound credential
+    Admin,
+    /// Synced credentials are refused at registration and authentication
+    All,
+}
@@ -0,0 +1,388 @@
+//! WebAuthn extensions
+//!
+//! Extensions are requested in the `extensions` member of the creation and
+//! request options.  The results come back in two places:
+//!
+//! - client extension results, from `getClientExtensionResults()`, as JSON
+//! - authenticator extension outputs, as a CBOR map at the end of the
+//!   authenticator data when the ED flag is set
+//!
+//! Extensions the server does not model are carried in `other`, so that
+//! they can be requested and inspected before they get typed fields.
+//!
+use base64urlsafedata::Base64UrlSafeData;
+use serde::{Deserialize, Serialize};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+use super::{Credential, CredentialProtectionPolicy, LargeBlobSupport, WebauthnPolicy};
+use crate::errors::Error;
+
+/// The `extensions` member of the creation and request options
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationExtensionsClientInputs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prf: Option<AuthenticationExtensionsPrfInputs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub large_blob: Option<AuthenticationExtensionsLargeBlobInputs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_protection_policy: Option<CredentialProtectionPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enforce_credential_protection_policy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_pin_length: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub appid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub appid_exclude: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cred_props: Option<bool>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
+
+impl AuthenticationExtensionsClientInputs {
+    /// The extensions the policy asks for at registration
+    pub fn for_registration(policy: &WebauthnPolicy) -> Self {
+        // Always ask whether the credential is discoverable
+        let mut inputs = Self {
+            cred_props: Some(true),
+            ..Default::default()
+        };
+        if policy.rp.prf {
+            // Only ask whether PRF is supported.  Salts are assigned once it is.
+            inputs.prf = Some(AuthenticationExtensionsPrfInputs::default());
+        }
+        if let Some(support) = policy.registration.large_blob.support() {
+            inputs.large_blob = Some(AuthenticationExtensionsLargeBlobInputs {
+                support: Some(support),
+                ..Default::default()
+            });
+        }
+        let registration = &policy.registration;
+        if registration.cred_protect > CredentialProtectionPolicy::default() {
+            // Enforced, so that an authenticator without support fails
+            // creation rather than silently ignoring the level
+            inputs.credential_protection_policy = Some(registration.cred_protect);
+            inputs.enforce_credential_protection_policy = Some(true);
+        }
+        if registration.min_pin_length > 0 {
+            inputs.min_pin_length = Some(true);
+        }
+        // Keep users with a legacy U2F credential from registering it again
+        inputs.appid_exclude = policy.rp.app_id.clone();
+        inputs
+    }
+
+    /// The extensions the policy asks for at authentication, given the
+    /// credentials that are allowed.
+    pub fn for_authentication(policy: &WebauthnPolicy, credentials: &[Credential]) -> Self {
+        let mut inputs = Self::default();
+        if policy.rp.prf {
+            let eval_by_credential: BTreeMap<String, AuthenticationExtensionsPrfValues> =
+                credentials
+                    .iter()
+                    .filter_map(|cred| {
+                        AuthenticationExtensionsPrfValues::for_credential(cred)
+                            .map(|values| (cred.id.to_string(), values))
+                    })
+                    .collect();
+            if !eval_by_credential.is_empty() {
+                inputs.prf = Some(AuthenticationExtensionsPrfInputs {
+                    eval: None,
+                    eval_by_credential: Some(eval_by_credential),
+                });
+            }
+        }
+        inputs.appid = policy.rp.app_id.clone();
+        inputs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Request an extension the server does not model
+    pub fn with_other(mut self, name: &str, input: serde_json::Value) -> Self {
+        self.other.insert(name.to_string(), input);
+        self
+    }
+}
+
+/// The result of `getClientExtensionResults()`.  Binary values are base64url
+/// encoded by the client.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationExtensionsClientOutputs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prf: Option<AuthenticationExtensionsPrfOutputs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub large_blob: Option<AuthenticationExtensionsLargeBlobOutputs>,
+    /// True if the client asserted with the AppID in place of the RP id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub appid: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub appid_exclude: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cred_props: Option<CredentialPropertiesOutput>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
+
+impl AuthenticationExtensionsClientOutputs {
+    /// True if the authenticator created the credential with PRF support
+    pub fn is_prf_enabled(&self) -> bool {
+        self.prf.as_ref().and_then(|prf| prf.enabled) == Some(true)
+    }
+
+    /// The `rk` credential property, if the client knows it
+    pub fn is_discoverable(&self) -> Option<bool> {
+        self.cred_props.as_ref().and_then(|props| props.rk)
+    }
+
+    /// True if the credential was created with large blob storage
+    pub fn is_large_blob_supported(&self) -> bool {
+        self.large_blob.as_ref().and_then(|lb| lb.supported) == Some(true)
+    }
+
+    /// Fails unless the client reports that a requested large blob write
+    /// succeeded.
+    pub fn verify_large_blob_written(&self) -> Result<(), Error> {
+        match self.large_blob.as_ref().and_then(|lb| lb.written) {
+            Some(true) => Ok(()),
+            Some(false) => Err(Error::LargeBlobError("write failed".to_string())),
+            None => Err(Error::LargeBlobError("write was not reported".to_string())),
+        }
+    }
+}
+
+/// The salts a PRF is evaluated with.  `second` lets a client derive the old
+/// and new keys in one ceremony while a salt is rotated.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationExtensionsPrfValues {
+    pub first: Base64UrlSafeData,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub second: Option<Base64UrlSafeData>,
+}
+
+impl AuthenticationExtensionsPrfValues {
+    /// The salts stored with a credential, if PRF is enabled for it
+    pub fn for_credential(credential: &Credential) -> Option<Self> {
+        if !credential.prf_enabled {
+            return None;
+        }
+        credential.prf_salt.as_ref().map(|salt| Self {
+            first: salt.clone(),
+            second: credential.prf_next_salt.clone(),
+        })
+    }
+}
+
+/// The `prf` extension input.  Empty at registration, which only asks
+/// whether the authenticator supports it.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationExtensionsPrfInputs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval: Option<AuthenticationExtensionsPrfValues>,
+    /// Salts keyed by base64url credential id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval_by_credential: Option<BTreeMap<String, AuthenticationExtensionsPrfValues>>,
+}
+
+/// The `prf` extension output.  The PRF results are key material for the
+/// client, so the client does not send them, and they are not modelled here.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationExtensionsPrfOutputs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+}
+
+/// The `largeBlob` extension input.  `support` is only valid at
+/// registration, `read` and `write` only at authentication.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationExtensionsLargeBlobInputs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub support: Option<LargeBlobSupport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write: Option<Base64UrlSafeData>,
+}
+
+/// The `largeBlob` extension output.  A blob that was read is only of use
+/// to the client, so the client does not send it.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationExtensionsLargeBlobOutputs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supported: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub written: Option<bool>,
+}
+
+/// The `credProps` extension output
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialPropertiesOutput {
+    /// True if the credential is discoverable (a resident key)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rk: Option<bool>,
+}
+
+/// The CBOR map of extension outputs in the authenticator data, keyed by
+/// extension identifier.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AuthenticatorExtensionOutputs(pub BTreeMap<String, Value>);
+
+impl TryFrom<&[u8]> for AuthenticatorExtensionOutputs {
+    type Error = Error;
+    fn try_from(data: &[u8]) -> Result<Self, Error> {
+        let map: BTreeMap<String, Value> = serde_cbor::from_slice(data).map_err(|e| {
+            log::trace!("Failed to parse extension outputs: {}", e);
+            Error::AuthenticatorDataDeserialize("EXTENSIONS".to_string())
+        })?;
+        Ok(Self(map))
+    }
+}
+
+impl AuthenticatorExtensionOutputs {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some(Value::Bool(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_integer(&self, name: &str) -> Option<i128> {
+        match self.get(name) {
+            Some(Value::Integer(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bytes(&self, name: &str) -> Option<&[u8]> {
+        match self.get(name) {
+            Some(Value::Bytes(value)) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authenticator_outputs() -> Result<(), Error> {
+        // {"credProtect": 2, "hmac-secret": true}
+        let data: Vec<u8> = vec![
+            0xa2, 0x6b, b'c', b'r', b'e', b'd', b'P', b'r', b'o', b't', b'e', b'c', b't', 0x02,
+            0x6b, b'h', b'm', b'a', b'c', b'-', b's', b'e', b'c', b'r', b'e', b't', 0xf5,
+        ];
+        let outputs = AuthenticatorExtensionOutputs::try_from(data.as_slice())?;
+        assert_eq!(outputs.get_integer("credProtect"), Some(2));
+        assert_eq!(outputs.get_bool("hmac-secret"), Some(true));
+        assert_eq!(outputs.get_bool("credProtect"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let data: Vec<u8> = vec![0xa0, 0x00];
+        assert!(AuthenticatorExtensionOutputs::try_from(data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_prf_outputs() {
+        let json = r#"{"prf": {"enabled": true}}"#;
+        let outputs: AuthenticationExtensionsClientOutputs =
+            serde_json::from_str(json).expect("oops");
+        assert!(outputs.is_prf_enabled());
+        assert!(outputs.other.is_empty());
+
+        let outputs = AuthenticationExtensionsClientOutputs::default();
+        assert!(!outputs.is_prf_enabled());
+    }
+
+    #[test]
+    fn test_prf_inputs() {
+        let mut values = BTreeMap::new();
+        values.insert(
+            "AQID".to_string(),
+            AuthenticationExtensionsPrfValues {
+                first: Base64UrlSafeData(vec![1, 2, 3]),
+                second: None,
+            },
+        );
+        let inputs = AuthenticationExtensionsClientInputs {
+            prf: Some(AuthenticationExtensionsPrfInputs {
+                eval: None,
+                eval_by_credential: Some(values),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&inputs).expect("oops"),
+            r#"{"prf":{"evalByCredential":{"AQID":{"first":"AQID"}}}}"#
+        );
+    }
+
+    #[test]
+    fn test_cred_props_outputs() {
+        let json = r#"{"credProps": {"rk": false}}"#;
+        let outputs: AuthenticationExtensionsClientOutputs =
+            serde_json::from_str(json).expect("oops");
+        assert_eq!(outputs.is_discoverable(), Some(false));
+
+        let json = r#"{"credProps": {}}"#;
+        let outputs: AuthenticationExtensionsClientOutputs =
+            serde_json::from_str(json).expect("oops");
+        assert_eq!(outputs.is_discoverable(), None);
+    }
+
+    #[test]
+    fn test_large_blob_outputs() {
+        let json = r#"{"largeBlob": {"supported": true}}"#;
+        let outputs: AuthenticationExtensionsClientOutputs =
+            serde_json::from_str(json).expect("oops");
+        assert!(outputs.is_large_blob_supported());
+
+        let json = r#"{"largeBlob": {"written": true}}"#;
+        let outputs: AuthenticationExtensionsClientOutputs =
+            serde_json::from_str(json).expect("oops");
+        assert!(outputs.verify_large_blob_written().is_ok());
+
+        let json = r#"{"largeBlob": {"written": false}}"#;
+        let outputs: AuthenticationExtensionsClientOutputs =
+            serde_json::from_str(json).expect("oops");
+        assert!(outputs.verify_large_blob_written().is_err());
+
+        let outputs = AuthenticationExtensionsClientOutputs::default();
+        assert!(outputs.verify_large_blob_written().is_err());
+    }
+
+    #[test]
+    fn test_client_outputs() {
+        let json = r#"{"unknown": {"enabled": true}}"#;
+        let outputs: AuthenticationExtensionsClientOutputs =
+            serde_json::from_str(json).expect("oops");
+        assert!(outputs.other.contains_key("unknown"));
+
+        let inputs = AuthenticationExtensionsClientInputs::default();
+        assert!(inputs.is_empty());
+        let inputs = inputs.with_other("unknown", serde_json::json!(true));
+        assert_eq!(
+            serde_json::to_string(&inputs).expect("oops"),
+            r#"{"unknown":true}"#
+        );
+    }
+}
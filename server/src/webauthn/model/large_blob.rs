@@ -0,0 +1,35 @@
+//! Store data on the authenticator with the `largeBlob` extension
+//!
+//! Support is requested at registration.  Reads and writes happen during an
+//! assertion that allows a single credential.
+//!
+use serde::{Deserialize, Serialize};
+
+/// The `support` member of the `largeBlob` registration input
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LargeBlobSupport {
+    Required,
+    Preferred,
+}
+
+/// Whether the policy requests `largeBlob` support at registration
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LargeBlobRequirement {
+    #[default]
+    None,
+    Preferred,
+    Required,
+}
+
+impl LargeBlobRequirement {
+    /// The registration input, if support is requested at all
+    pub fn support(&self) -> Option<LargeBlobSupport> {
+        match self {
+            LargeBlobRequirement::None => None,
+            LargeBlobRequirement::Preferred => Some(LargeBlobSupport::Preferred),
+            LargeBlobRequirement::Required => Some(LargeBlobSupport::Required),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub use allowed_origin::*;
 pub use attestatin_conveyence_preference::*;
 pub use attestation::*;
 pub use attestation_format_identifier::*;
@@ -11,6 +12,11 @@ pub use authenticator_transport::*;
 pub use client_data::*;
 pub use cose_algorithm::*;
 pub use credential::*;
+pub use credential_protection::*;
+pub use device_bound_requirement::*;
+pub use extensions::*;
+pub use large_blob::*;
+pub use policy_violation::*;
 pub use public_key_credential::*;
 pub use public_key_credential_creation_options::*;
 pub use public_key_credential_descriptor::*;
@@ -23,6 +29,7 @@ pub use user_entity::*;
 pub use user_verification_requirement::*;
 pub use webauthn_policy::*;
 
+pub mod allowed_origin;
 pub mod attestatin_conveyence_preference;
 pub mod attestation;
 pub mod attestation_format_identifier;
@@ -36,6 +43,11 @@ pub mod authenticator_transport;
 pub mod client_data;
 pub mod cose_algorithm;
 pub mod credential;
+pub mod credential_protection;
+pub mod device_bound_requirement;
+pub mod extensions;
+pub mod large_blob;
+pub mod policy_violation;
 pub mod public_key_credential;
 pub mod public_key_credential_creation_options;
 pub mod public_key_credential_descriptor;
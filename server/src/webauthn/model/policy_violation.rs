@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// A setting that makes a WebAuthn policy invalid.  `field` is the path of
+/// the setting as it is patched, e.g. `registration.timeout`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyViolation {
+    pub field: String,
+    pub message: String,
+}
+
+impl PolicyViolation {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
@@ -10,10 +10,16 @@ pub struct GetClientExtensionResults {}
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct CreationPublicKeyCredential {
+    /// Not reported by every browser
+    #[serde(default)]
+    pub authenticator_attachment: Option<AuthenticatorAttachment>,
     pub id: Base64UrlSafeData,
     pub raw_id: Base64UrlSafeData,
     pub response: AuthenticatorAttestationResponse,
     pub get_client_extension_results: GetClientExtensionResults,
+    /// The result of `getClientExtensionResults()`, added by the client
+    #[serde(default)]
+    pub client_extension_results: AuthenticationExtensionsClientOutputs,
     #[serde(rename = "type")]
     pub type_: PublicKeyCredentialType, // this wil always be "public-key"
 }
@@ -28,6 +34,9 @@ pub struct AssertionPublicKeyCredential {
     #[serde(rename = "type")]
     pub type_: PublicKeyCredentialType, // this wil always be "public-key"
     pub get_client_extension_results: GetClientExtensionResults,
+    /// The result of `getClientExtensionResults()`, added by the client
+    #[serde(default)]
+    pub client_extension_results: AuthenticationExtensionsClientOutputs,
 }
 
 #[cfg(test)]
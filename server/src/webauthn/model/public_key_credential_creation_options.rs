@@ -17,6 +17,10 @@ pub struct PublicKeyCredentialCreationOptions {
     pub attestation: Option<AttestationConveyancePreference>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub authenticator_selection: Option<AuthenticatorSelectionCriteria>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_credentials: Option<Vec<PublicKeyCredentialDiscriptor>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<AuthenticationExtensionsClientInputs>,
 }
 
 impl PublicKeyCredentialCreationOptions {
@@ -55,10 +59,14 @@ impl TryFrom<(&WebauthnPolicy, &UserEntity, &Base64UrlSafeData)>
         let user = input.1;
         let challenge = input.2;
 
-        let user = UserEntity::builder()
+        // Keep the user handle if the caller already assigned one
+        let mut builder = UserEntity::builder()
             .with_name(&user.name)
-            .with_display_name(&user.display_name)
-            .build()?;
+            .with_display_name(&user.display_name);
+        if let Some(id) = &user.id {
+            builder = builder.with_id(id.as_ref());
+        }
+        let user = builder.build()?;
 
         let options = PublicKeyCredentialCreationOptionsBuilder::from(policy)
             .with_challenge(challenge)
@@ -76,6 +84,8 @@ pub struct PublicKeyCredentialCreationOptionsBuilder {
     pub_key_cred_params: Option<Vec<PublicKeyCredentialParameters>>,
     attestation: Option<AttestationConveyancePreference>,
     authenticator_selection: Option<AuthenticatorSelectionCriteria>,
+    exclude_credentials: Option<Vec<PublicKeyCredentialDiscriptor>>,
+    extensions: Option<AuthenticationExtensionsClientInputs>,
 }
 
 impl Default for PublicKeyCredentialCreationOptionsBuilder {
@@ -88,10 +98,11 @@ impl Default for PublicKeyCredentialCreationOptionsBuilder {
 impl From<&WebauthnPolicy> for PublicKeyCredentialCreationOptionsBuilder {
     fn from(policy: &WebauthnPolicy) -> Self {
         let rp = RpEntity::from(policy);
-        let pub_key_cred_params = PublicKeyCredentialParameters::from(policy);
-        let attestation = policy.attestation.clone();
-        let timeout = policy.timeout;
+        let pub_key_cred_params = PublicKeyCredentialParameters::for_policy(policy);
+        let attestation = policy.registration.attestation.clone();
+        let timeout = policy.registration.timeout;
         let authenticator_selection = AuthenticatorSelectionCriteria::from(policy);
+        let extensions = AuthenticationExtensionsClientInputs::for_registration(policy);
 
         PublicKeyCredentialCreationOptionsBuilder::default()
             .with_attestation(attestation)
@@ -99,6 +110,7 @@ impl From<&WebauthnPolicy> for PublicKeyCredentialCreationOptionsBuilder {
             .with_rp(rp)
             .with_pub_key_cred_params(pub_key_cred_params)
             .with_authenticator_selection(authenticator_selection)
+            .with_extensions(extensions)
     }
 }
 
@@ -113,6 +125,8 @@ impl PublicKeyCredentialCreationOptionsBuilder {
             timeout: None,
             attestation: None,
             authenticator_selection: None,
+            exclude_credentials: None,
+            extensions: None,
         }
     }
 
@@ -140,8 +154,9 @@ impl PublicKeyCredentialCreationOptionsBuilder {
         self
     }
 
-    pub fn with_pub_key_cred_params(mut self, params: PublicKeyCredentialParameters) -> Self {
-        self.pub_key_cred_params = Some(vec![params]);
+    /// The credential parameters to offer, most preferred first
+    pub fn with_pub_key_cred_params(mut self, params: Vec<PublicKeyCredentialParameters>) -> Self {
+        self.pub_key_cred_params = Some(params);
         self
     }
 
@@ -150,6 +165,22 @@ impl PublicKeyCredentialCreationOptionsBuilder {
         self
     }
 
+    /// Credentials the user already has, so that an authenticator holding
+    /// one of them is not registered again.
+    pub fn with_exclude_credentials(
+        mut self,
+        exclude_credentials: Vec<PublicKeyCredentialDiscriptor>,
+    ) -> Self {
+        self.exclude_credentials = Some(exclude_credentials);
+        self
+    }
+
+    /// Extensions to request.  Omitted from the options if empty.
+    pub fn with_extensions(mut self, extensions: AuthenticationExtensionsClientInputs) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
     pub fn build(&self) -> Result<PublicKeyCredentialCreationOptions, Error> {
         if self.user.is_none() {
             return Err(Error::RegistrationChallengResponseBuildError);
@@ -168,6 +199,43 @@ impl PublicKeyCredentialCreationOptionsBuilder {
             attestation: self.attestation.clone(),
             authenticator_selection: self.authenticator_selection.clone(),
             timeout: self.timeout,
+            exclude_credentials: self.exclude_credentials.clone(),
+            extensions: self.extensions.clone().filter(|e| !e.is_empty()),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exclude_legacy_credentials() {
+        let mut policy = crate::config::AppConfig::default().webauthn;
+        let app_id = "https://login.example.com/u2f/app-id.json".to_string();
+        policy.rp.app_id = Some(app_id.clone());
+        let user = UserEntity::builder()
+            .with_name("u2f-user")
+            .with_display_name(&Some("U2F User".to_string()))
+            .build()
+            .expect("oops");
+        let legacy = PublicKeyCredentialDiscriptor {
+            type_: PublicKeyCredentialType::PublicKey,
+            id: Base64UrlSafeData(vec![1, 2, 3, 4]),
+            transports: Some(vec![AuthenticatorTransport::USB]),
+        };
+
+        let options = PublicKeyCredentialCreationOptionsBuilder::from(&policy)
+            .with_user(user)
+            .with_exclude_credentials(vec![legacy.clone()])
+            .build()
+            .expect("oops");
+
+        // The authenticator checks the excluded ids against the AppID too
+        let json = serde_json::to_value(&options).expect("oops");
+        let excluded = serde_json::to_value(vec![legacy]).expect("oops");
+        assert_eq!(json["excludeCredentials"], excluded);
+        assert_eq!(json["excludeCredentials"][0]["transports"][0], "usb");
+        assert_eq!(json["extensions"]["appidExclude"], app_id);
+    }
+}
@@ -21,7 +21,7 @@ impl TryFrom<&Credential> for PublicKeyCredentialDiscriptor {
         Ok(Self {
             type_: credential.type_.clone(),
             id: credential.id.clone(),
-            transports: None,
+            transports: credential.transports.clone(),
         })
     }
 }
@@ -16,12 +16,18 @@ impl PublicKeyCredentialParameters {
     }
 }
 
-impl From<&WebauthnPolicy> for PublicKeyCredentialParameters {
-    fn from(policy: &WebauthnPolicy) -> Self {
-        Self {
-            key_type: policy.key_type.clone(),
-            alg: policy.alg,
-        }
+impl PublicKeyCredentialParameters {
+    /// One entry for each algorithm in the registration policy, in the policy's order
+    pub fn for_policy(policy: &WebauthnPolicy) -> Vec<Self> {
+        policy
+            .registration
+            .algs
+            .iter()
+            .map(|alg| Self {
+                key_type: policy.registration.key_type.clone(),
+                alg: *alg,
+            })
+            .collect()
     }
 }
 
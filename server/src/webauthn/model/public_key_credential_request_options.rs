@@ -16,6 +16,8 @@ pub struct PublicKeyCredentialRequestOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub allow_credentials: Option<Vec<PublicKeyCredentialDiscriptor>>,
     pub user_verification: UserVerificationRequirement,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<AuthenticationExtensionsClientInputs>,
 }
 
 impl PublicKeyCredentialRequestOptions {
@@ -24,21 +26,28 @@ impl PublicKeyCredentialRequestOptions {
     }
 }
 
-/// Encapsulate the most common builder use case
-impl TryFrom<(&WebauthnPolicy, &Credential, &Base64UrlSafeData)>
+/// Encapsulate the most common builder use case.  Every credential the user
+/// has registered is allowed.
+impl TryFrom<(&WebauthnPolicy, &[Credential], &Base64UrlSafeData)>
     for PublicKeyCredentialRequestOptions
 {
     type Error = Error;
     fn try_from(
-        input: (&WebauthnPolicy, &Credential, &Base64UrlSafeData),
+        input: (&WebauthnPolicy, &[Credential], &Base64UrlSafeData),
     ) -> Result<Self, Self::Error> {
         let policy = input.0;
-        let credential = input.1;
+        let credentials = input.1;
         let challenge = input.2;
-        let allow_credentials = vec![PublicKeyCredentialDiscriptor::try_from(credential)?];
+        let allow_credentials = credentials
+            .iter()
+            .map(PublicKeyCredentialDiscriptor::try_from)
+            .collect::<Result<Vec<_>, Error>>()?;
+        let extensions =
+            AuthenticationExtensionsClientInputs::for_authentication(policy, credentials);
         PublicKeyCredentialRequestOptionsBuilder::from(policy)
             .with_challenge(challenge)
             .with_allow_credentials(allow_credentials)
+            .with_extensions(extensions)
             .build()
     }
 }
@@ -49,13 +58,15 @@ pub struct PublicKeyCredentialRequestOptionsBuilder {
     rp_id: Option<String>,
     allow_credentials: Option<Vec<PublicKeyCredentialDiscriptor>>,
     user_verification: Option<UserVerificationRequirement>,
+    extensions: Option<AuthenticationExtensionsClientInputs>,
 }
 
 /// Build the options builder from WebAuthnPolicy.
 impl From<&WebauthnPolicy> for PublicKeyCredentialRequestOptionsBuilder {
     fn from(policy: &WebauthnPolicy) -> Self {
         PublicKeyCredentialRequestOptionsBuilder::default()
-            .with_user_verification(&policy.user_verification)
+            .with_user_verification(&policy.authentication.user_verification)
+            .with_timeout(policy.authentication.timeout)
     }
 }
 
@@ -73,6 +84,7 @@ impl PublicKeyCredentialRequestOptionsBuilder {
             rp_id: None,
             allow_credentials: None,
             user_verification: None,
+            extensions: None,
         }
     }
 
@@ -102,7 +114,47 @@ impl PublicKeyCredentialRequestOptionsBuilder {
         self
     }
 
+    /// Extensions to request.  Omitted from the options if empty.
+    pub fn with_extensions(mut self, extensions: AuthenticationExtensionsClientInputs) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
+    /// Ask the authenticator to return the credential's large blob
+    pub fn with_large_blob_read(mut self) -> Self {
+        let mut extensions = self.extensions.take().unwrap_or_default();
+        extensions.large_blob = Some(AuthenticationExtensionsLargeBlobInputs {
+            read: Some(true),
+            ..Default::default()
+        });
+        self.extensions = Some(extensions);
+        self
+    }
+
+    /// Ask the authenticator to store `blob` as the credential's large blob.
+    /// A write must allow exactly one credential.
+    pub fn with_large_blob_write(mut self, blob: &[u8]) -> Self {
+        let mut extensions = self.extensions.take().unwrap_or_default();
+        extensions.large_blob = Some(AuthenticationExtensionsLargeBlobInputs {
+            write: Some(Base64UrlSafeData(blob.to_vec())),
+            ..Default::default()
+        });
+        self.extensions = Some(extensions);
+        self
+    }
+
     pub fn build(&self) -> Result<PublicKeyCredentialRequestOptions, Error> {
+        let large_blob_write = self
+            .extensions
+            .as_ref()
+            .and_then(|e| e.large_blob.as_ref())
+            .is_some_and(|lb| lb.write.is_some());
+        let allowed = self.allow_credentials.as_ref().map_or(0, |a| a.len());
+        if large_blob_write && allowed != 1 {
+            return Err(Error::BuildError(
+                "A large blob write must allow exactly one credential".to_string(),
+            ));
+        }
         let challenge = self
             .challenge
             .clone()
@@ -117,6 +169,7 @@ impl PublicKeyCredentialRequestOptionsBuilder {
             rp_id: self.rp_id.clone(),
             allow_credentials: self.allow_credentials.clone(),
             user_verification,
+            extensions: self.extensions.clone().filter(|e| !e.is_empty()),
         })
     }
 }
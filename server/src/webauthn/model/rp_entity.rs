@@ -29,7 +29,7 @@ impl RpEntity {
 
 impl From<&WebauthnPolicy> for RpEntity {
     fn from(policy: &WebauthnPolicy) -> Self {
-        Self::new(policy.rp_name.as_ref())
+        Self::new(policy.rp.name.as_ref())
     }
 }
 
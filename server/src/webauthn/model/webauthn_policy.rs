@@ -1,181 +1,858 @@
-use serde::{Deserialize, Serialize};
+use chrono::Duration;
+use serde::{Deserialize, Deserializer, Serialize};
 use url::Url;
 
 use super::*;
 use crate::errors::Error;
+use crate::utils::{is_registrable_rp_id, is_within_rp_id, registrable_label};
 
+/// The WebAuthn policy, in a section shared by both ceremonies and one
+/// section for each ceremony.  Policies stored before the split into sections
+/// are flat, and are read with the same values for both ceremonies.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "camelCase")]
+#[serde(from = "StoredWebauthnPolicy")]
 pub struct WebauthnPolicy {
-    pub rp_id: String,
-    pub rp_name: String,
+    pub rp: RelyingPartyPolicy,
+    pub registration: RegistrationPolicy,
+    pub authentication: AuthenticationPolicy,
+}
+
+/// Policy for the Relying Party, shared by registration and authentication
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RelyingPartyPolicy {
+    pub id: String,
+    pub name: String,
+    /// The origins clients may run on.  Stored policies that predate the list
+    /// have a single `origin`.
+    #[serde(alias = "origin", deserialize_with = "deserialize_origins")]
+    pub origins: Vec<AllowedOrigin>,
+    /// Web origins on other domains that may use this RP id, for WebAuthn
+    /// Related Origin Requests.  They are served at `/.well-known/webauthn`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related_origins: Vec<AllowedOrigin>,
+    /// Accept ceremonies from an iframe whose top level page is on another
+    /// origin, i.e. `crossOrigin` is true in the client data
+    #[serde(default)]
+    pub cross_origin: bool,
+    /// The top level sites that may embed a cross-origin ceremony.  When
+    /// empty, any `topOrigin` is accepted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_origins: Vec<AllowedOrigin>,
+    /// The U2F AppID that legacy credentials were registered under, for the
+    /// `appid` and `appidExclude` extensions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    /// Milliseconds a challenge is still accepted after the ceremony's
+    /// `timeout`, to allow for network latency.
+    #[serde(default = "default_challenge_grace")]
+    pub challenge_grace: usize,
+    /// Where synced (backup eligible) credentials are refused
+    #[serde(default)]
+    pub device_bound: DeviceBoundRequirement,
+    /// Warn a user whose only credential is not backed up
+    #[serde(default)]
+    pub warn_not_backed_up: bool,
+    /// Request the `prf` extension, so that clients can derive encryption keys
+    #[serde(default)]
+    pub prf: bool,
+}
+
+/// Policy for creating credentials
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationPolicy {
     pub key_type: PublicKeyCredentialType,
-    pub alg: COSEAlgorithm,
+    /// The algorithms offered in `pubKeyCredParams`, most preferred first.
+    /// Stored policies that predate the list have a single `alg`.
+    #[serde(alias = "alg", deserialize_with = "deserialize_algs")]
+    pub algs: Vec<COSEAlgorithm>,
     pub authenticator_attachment: AuthenticatorAttachment,
     pub resident_key: ResidentKeyRequirement,
     pub user_verification: UserVerificationRequirement,
-    pub origin: Url,
     pub attestation: AttestationConveyancePreference,
     pub timeout: usize,
-    pub validate_sign_count: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub authenticator_transports: Option<Vec<AuthenticatorTransport>>,
+    /// Request `largeBlob` support when registering credentials
+    #[serde(default)]
+    pub large_blob: LargeBlobRequirement,
+    /// The `credProtect` level new credentials must have.  The extension is
+    /// only requested above `userVerificationOptional`.
+    #[serde(default)]
+    pub cred_protect: CredentialProtectionPolicy,
+    /// The PIN length new credentials must report with `minPinLength`, or 0
+    #[serde(default)]
+    pub min_pin_length: u32,
+}
+
+/// Policy for getting assertions
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationPolicy {
+    pub user_verification: UserVerificationRequirement,
+    pub timeout: usize,
+    pub validate_sign_count: bool,
+}
+
+pub const DEFAULT_CHALLENGE_GRACE: usize = 30000;
+
+/// Clients only need to accept related origins with this many distinct
+/// registrable domain labels
+pub const MAX_RELATED_ORIGIN_LABELS: usize = 5;
+
+fn default_challenge_grace() -> usize {
+    DEFAULT_CHALLENGE_GRACE
+}
+
+/// Accept either a single value or a list of them
+fn deserialize_one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => Ok(vec![value]),
+        OneOrMany::Many(values) => Ok(values),
+    }
+}
+
+/// Tell an explicit `null`, as `Some(None)`, from a missing field, as `None`
+fn deserialize_nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Accept either a single algorithm or a list of them
+pub fn deserialize_algs<'de, D>(deserializer: D) -> Result<Vec<COSEAlgorithm>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_one_or_many(deserializer)
+}
+
+/// Accept either a single origin or a list of them
+pub fn deserialize_origins<'de, D>(deserializer: D) -> Result<Vec<AllowedOrigin>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_one_or_many(deserializer)
+}
+
+/// A policy as it may be stored, with sections or flat
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredWebauthnPolicy {
+    Sections {
+        rp: RelyingPartyPolicy,
+        registration: RegistrationPolicy,
+        authentication: AuthenticationPolicy,
+    },
+    Flat(FlatWebauthnPolicy),
+}
+
+/// The policy before it was split into sections
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FlatWebauthnPolicy {
+    rp_id: String,
+    rp_name: String,
+    key_type: PublicKeyCredentialType,
+    #[serde(alias = "alg", deserialize_with = "deserialize_algs")]
+    algs: Vec<COSEAlgorithm>,
+    authenticator_attachment: AuthenticatorAttachment,
+    resident_key: ResidentKeyRequirement,
+    user_verification: UserVerificationRequirement,
+    origin: Url,
+    attestation: AttestationConveyancePreference,
+    timeout: usize,
+    #[serde(default = "default_challenge_grace")]
+    challenge_grace: usize,
+    validate_sign_count: bool,
+    authenticator_transports: Option<Vec<AuthenticatorTransport>>,
+    #[serde(default)]
+    device_bound: DeviceBoundRequirement,
+    #[serde(default)]
+    warn_not_backed_up: bool,
+    #[serde(default)]
+    prf: bool,
+    #[serde(default)]
+    large_blob: LargeBlobRequirement,
+    #[serde(default)]
+    cred_protect: CredentialProtectionPolicy,
+    #[serde(default)]
+    min_pin_length: u32,
+    #[serde(default)]
+    app_id: Option<String>,
+}
+
+impl From<StoredWebauthnPolicy> for WebauthnPolicy {
+    fn from(stored: StoredWebauthnPolicy) -> Self {
+        match stored {
+            StoredWebauthnPolicy::Sections {
+                rp,
+                registration,
+                authentication,
+            } => Self {
+                rp,
+                registration,
+                authentication,
+            },
+            // Both ceremonies had the same user verification and timeout
+            StoredWebauthnPolicy::Flat(flat) => Self {
+                rp: RelyingPartyPolicy {
+                    id: flat.rp_id,
+                    name: flat.rp_name,
+                    origins: vec![AllowedOrigin::Web(flat.origin)],
+                    related_origins: vec![],
+                    cross_origin: false,
+                    top_origins: vec![],
+                    app_id: flat.app_id,
+                    challenge_grace: flat.challenge_grace,
+                    device_bound: flat.device_bound,
+                    warn_not_backed_up: flat.warn_not_backed_up,
+                    prf: flat.prf,
+                },
+                registration: RegistrationPolicy {
+                    key_type: flat.key_type,
+                    algs: flat.algs,
+                    authenticator_attachment: flat.authenticator_attachment,
+                    resident_key: flat.resident_key,
+                    user_verification: flat.user_verification.clone(),
+                    attestation: flat.attestation,
+                    timeout: flat.timeout,
+                    authenticator_transports: flat.authenticator_transports,
+                    large_blob: flat.large_blob,
+                    cred_protect: flat.cred_protect,
+                    min_pin_length: flat.min_pin_length,
+                },
+                authentication: AuthenticationPolicy {
+                    user_verification: flat.user_verification,
+                    timeout: flat.timeout,
+                    validate_sign_count: flat.validate_sign_count,
+                },
+            },
+        }
+    }
 }
 
 impl WebauthnPolicy {
+    /// How long a challenge issued for registration may be used for
+    pub fn registration_challenge_ttl(&self) -> Duration {
+        let ttl = self.registration.timeout + self.rp.challenge_grace;
+        Duration::milliseconds(ttl as i64)
+    }
+
+    /// How long a challenge issued for authentication may be used for
+    pub fn authentication_challenge_ttl(&self) -> Duration {
+        let ttl = self.authentication.timeout + self.rp.challenge_grace;
+        Duration::milliseconds(ttl as i64)
+    }
+
+    /// Patch the sections that are in the builder, and validate the result
     pub fn update(&mut self, builder: WebauthnPolicyBuilder) -> Result<(), Error> {
-        if builder.rp_id.is_some() {
-            self.rp_id = builder.rp_id.unwrap();
+        self.apply(builder);
+        self.validate()
+    }
+
+    /// Patch the sections that are in the builder, without validating
+    pub fn apply(&mut self, builder: WebauthnPolicyBuilder) {
+        if let Some(rp) = builder.rp {
+            self.rp.apply(rp);
+        }
+        if let Some(registration) = builder.registration {
+            self.registration.apply(registration);
+        }
+        if let Some(authentication) = builder.authentication {
+            self.authentication.apply(authentication);
+        }
+    }
+
+    /// Every setting that makes the policy invalid, in all sections
+    pub fn violations(&self) -> Vec<PolicyViolation> {
+        let mut violations = self.rp.violations();
+        violations.extend(self.registration.violations());
+        violations.extend(self.authentication.violations());
+        violations
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        check_violations(self.violations())
+    }
+}
+
+fn check_violations(violations: Vec<PolicyViolation>) -> Result<(), Error> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::PolicyViolations(violations))
+    }
+}
+
+impl RelyingPartyPolicy {
+    /// True if a client running on `origin` may use this RP, either directly
+    /// or as a related origin
+    pub fn allows_origin(&self, origin: &Url) -> bool {
+        self.origins
+            .iter()
+            .chain(self.related_origins.iter())
+            .any(|allowed| allowed.matches(origin))
+    }
+
+    /// Check the `origin`, `crossOrigin` and `topOrigin` of the client data
+    pub fn verify_origin(&self, client_data: &ClientData) -> Result<(), Error> {
+        if !self.allows_origin(&client_data.origin) {
+            return Err(Error::BadOrigin);
+        }
+        if client_data.cross_origin != Some(true) {
+            return Ok(());
+        }
+        if !self.cross_origin {
+            return Err(Error::CrossOriginNotAllowed);
+        }
+        if self.top_origins.is_empty() {
+            return Ok(());
+        }
+        let top_origin = client_data.top_origin.as_ref().ok_or(Error::BadTopOrigin)?;
+        if !self.top_origins.iter().any(|top| top.matches(top_origin)) {
+            return Err(Error::BadTopOrigin);
+        }
+        Ok(())
+    }
+
+    /// The RP id must not be a public suffix, and every web origin must be
+    /// on the RP id or one of its subdomains.  Related origins must be exact
+    /// web origins, on no more than [MAX_RELATED_ORIGIN_LABELS] labels.
+    pub fn violations(&self) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        if !is_registrable_rp_id(&self.id) {
+            let message = format!("{} is not a registrable domain", self.id);
+            violations.push(PolicyViolation::new("rp.id", message));
+        }
+        if self.name.is_empty() {
+            violations.push(PolicyViolation::new("rp.name", "must not be empty"));
+        }
+        if self.origins.is_empty() {
+            violations.push(PolicyViolation::new("rp.origins", "must not be empty"));
+        }
+        for origin in &self.origins {
+            if let Some(host) = origin.host() {
+                if !is_within_rp_id(host, &self.id) {
+                    let message = format!("{} is not within rp.id {}", origin, self.id);
+                    violations.push(PolicyViolation::new("rp.origins", message));
+                }
+            }
+        }
+        let mut labels = Vec::new();
+        for origin in &self.related_origins {
+            let label = match origin {
+                AllowedOrigin::Web(url) => url.host_str().and_then(registrable_label),
+                _ => None,
+            };
+            match label {
+                Some(label) if !labels.contains(&label) => labels.push(label),
+                Some(_) => {}
+                None => {
+                    let message = format!("{} is not a web origin on a registrable domain", origin);
+                    violations.push(PolicyViolation::new("rp.relatedOrigins", message));
+                }
+            }
+        }
+        if labels.len() > MAX_RELATED_ORIGIN_LABELS {
+            let message = format!("use more than {} labels", MAX_RELATED_ORIGIN_LABELS);
+            violations.push(PolicyViolation::new("rp.relatedOrigins", message));
+        }
+        violations
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        check_violations(self.violations())
+    }
+
+    pub fn update(&mut self, builder: RelyingPartyPolicyBuilder) -> Result<(), Error> {
+        self.apply(builder);
+        self.validate()
+    }
+
+    fn apply(&mut self, builder: RelyingPartyPolicyBuilder) {
+        if let Some(id) = builder.id {
+            self.id = id;
+        }
+        if let Some(name) = builder.name {
+            self.name = name;
+        }
+        if let Some(origins) = builder.origins {
+            self.origins = origins;
+        }
+        if let Some(related_origins) = builder.related_origins {
+            self.related_origins = related_origins;
+        }
+        if let Some(cross_origin) = builder.cross_origin {
+            self.cross_origin = cross_origin;
+        }
+        if let Some(top_origins) = builder.top_origins {
+            self.top_origins = top_origins;
+        }
+        if let Some(app_id) = builder.app_id {
+            self.app_id = app_id;
+        }
+        if let Some(challenge_grace) = builder.challenge_grace {
+            self.challenge_grace = challenge_grace;
         }
-        if builder.rp_name.is_some() {
-            self.rp_name = builder.rp_name.unwrap();
+        if let Some(device_bound) = builder.device_bound {
+            self.device_bound = device_bound;
         }
-        if builder.key_type.is_some() {
-            self.key_type = builder.key_type.unwrap();
+        if let Some(warn_not_backed_up) = builder.warn_not_backed_up {
+            self.warn_not_backed_up = warn_not_backed_up;
         }
-        if builder.alg.is_some() {
-            self.alg = builder.alg.unwrap();
+        if let Some(prf) = builder.prf {
+            self.prf = prf;
         }
-        if builder.authenticator_attachment.is_some() {
-            self.authenticator_attachment = builder.authenticator_attachment.unwrap()
+    }
+}
+
+impl RegistrationPolicy {
+    /// True if a credential key with the COSE algorithm `alg` may be registered
+    pub fn allows_alg(&self, alg: i32) -> bool {
+        self.algs.iter().any(|allowed| *allowed as i32 == alg)
+    }
+
+    /// There must be an algorithm and a timeout, and the transports must be
+    /// ones the authenticator attachment can use
+    pub fn violations(&self) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        if self.algs.is_empty() {
+            violations.push(PolicyViolation::new(
+                "registration.algs",
+                "must not be empty",
+            ));
         }
-        if builder.resident_key.is_some() {
-            self.resident_key = builder.resident_key.unwrap();
+        if self.timeout == 0 {
+            violations.push(PolicyViolation::new(
+                "registration.timeout",
+                "must not be 0",
+            ));
         }
-        if builder.user_verification.is_some() {
-            self.user_verification = builder.user_verification.unwrap();
+        for transport in self.authenticator_transports.iter().flatten() {
+            if !self.authenticator_attachment.allows_transport(transport) {
+                let message = format!(
+                    "{:?} is not a transport of {:?} authenticators",
+                    transport, self.authenticator_attachment
+                );
+                let field = "registration.authenticatorTransports";
+                violations.push(PolicyViolation::new(field, message));
+            }
         }
-        if builder.origin.is_some() {
-            self.origin = builder.origin.unwrap();
+        violations
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        check_violations(self.violations())
+    }
+
+    pub fn update(&mut self, builder: RegistrationPolicyBuilder) -> Result<(), Error> {
+        self.apply(builder);
+        self.validate()
+    }
+
+    fn apply(&mut self, builder: RegistrationPolicyBuilder) {
+        if let Some(key_type) = builder.key_type {
+            self.key_type = key_type;
+        }
+        if let Some(algs) = builder.algs {
+            self.algs = algs;
+        }
+        if let Some(authenticator_attachment) = builder.authenticator_attachment {
+            self.authenticator_attachment = authenticator_attachment;
         }
-        if builder.attestation.is_some() {
-            self.attestation = builder.attestation.unwrap();
+        if let Some(resident_key) = builder.resident_key {
+            self.resident_key = resident_key;
         }
-        if builder.timeout.is_some() {
-            self.timeout = builder.timeout.unwrap();
+        if let Some(user_verification) = builder.user_verification {
+            self.user_verification = user_verification;
         }
-        if builder.validate_sign_count.is_some() {
-            self.validate_sign_count = builder.validate_sign_count.unwrap();
+        if let Some(attestation) = builder.attestation {
+            self.attestation = attestation;
+        }
+        if let Some(timeout) = builder.timeout {
+            self.timeout = timeout;
         }
         if builder.authenticator_transports.is_some() {
             self.authenticator_transports = builder.authenticator_transports
         }
-        Ok(())
+        if let Some(large_blob) = builder.large_blob {
+            self.large_blob = large_blob;
+        }
+        if let Some(cred_protect) = builder.cred_protect {
+            self.cred_protect = cred_protect;
+        }
+        if let Some(min_pin_length) = builder.min_pin_length {
+            self.min_pin_length = min_pin_length;
+        }
+    }
+}
+
+impl AuthenticationPolicy {
+    pub fn violations(&self) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        if self.timeout == 0 {
+            violations.push(PolicyViolation::new(
+                "authentication.timeout",
+                "must not be 0",
+            ));
+        }
+        violations
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        check_violations(self.violations())
+    }
+
+    pub fn update(&mut self, builder: AuthenticationPolicyBuilder) -> Result<(), Error> {
+        self.apply(builder);
+        self.validate()
+    }
+
+    fn apply(&mut self, builder: AuthenticationPolicyBuilder) {
+        if let Some(user_verification) = builder.user_verification {
+            self.user_verification = user_verification;
+        }
+        if let Some(timeout) = builder.timeout {
+            self.timeout = timeout;
+        }
+        if let Some(validate_sign_count) = builder.validate_sign_count {
+            self.validate_sign_count = validate_sign_count;
+        }
     }
 }
 
 /// WebauthnPolicyBuilder represents a modification of the builder
 /// pattern that ensures every element of WebauthnPolicy is explicitly
-/// defined before building.
+/// defined before building.  It is also the body of a policy patch, where
+/// only the sections and elements to change are present.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct WebauthnPolicyBuilder {
-    pub rp_id: Option<String>,
-    pub rp_name: Option<String>,
+    pub rp: Option<RelyingPartyPolicyBuilder>,
+    pub registration: Option<RegistrationPolicyBuilder>,
+    pub authentication: Option<AuthenticationPolicyBuilder>,
+}
+
+impl Default for WebauthnPolicyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebauthnPolicyBuilder {
+    pub fn new() -> Self {
+        Self {
+            rp: None,
+            registration: None,
+            authentication: None,
+        }
+    }
+
+    pub fn build(self) -> Result<WebauthnPolicy, Error> {
+        let rp = self
+            .rp
+            .ok_or_else(|| Error::EmptyWebauthnPolicy("rp".to_string()))?;
+        let registration = self
+            .registration
+            .ok_or_else(|| Error::EmptyWebauthnPolicy("registration".to_string()))?;
+        let authentication = self
+            .authentication
+            .ok_or_else(|| Error::EmptyWebauthnPolicy("authentication".to_string()))?;
+        Ok(WebauthnPolicy {
+            rp: rp.build()?,
+            registration: registration.build()?,
+            authentication: authentication.build()?,
+        })
+    }
+
+    pub fn with_rp(mut self, rp: RelyingPartyPolicyBuilder) -> Self {
+        self.rp = Some(rp);
+        self
+    }
+
+    pub fn with_registration(mut self, registration: RegistrationPolicyBuilder) -> Self {
+        self.registration = Some(registration);
+        self
+    }
+
+    pub fn with_authentication(mut self, authentication: AuthenticationPolicyBuilder) -> Self {
+        self.authentication = Some(authentication);
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RelyingPartyPolicyBuilder {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub origins: Option<Vec<AllowedOrigin>>,
+    pub related_origins: Option<Vec<AllowedOrigin>>,
+    pub cross_origin: Option<bool>,
+    pub top_origins: Option<Vec<AllowedOrigin>>,
+    /// `null` clears the AppID, a missing `appId` keeps it
+    #[serde(
+        default,
+        deserialize_with = "deserialize_nullable",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub app_id: Option<Option<String>>,
+    pub challenge_grace: Option<usize>,
+    pub device_bound: Option<DeviceBoundRequirement>,
+    pub warn_not_backed_up: Option<bool>,
+    pub prf: Option<bool>,
+}
+
+impl Default for RelyingPartyPolicyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RelyingPartyPolicyBuilder {
+    pub fn new() -> Self {
+        Self {
+            id: None,
+            name: None,
+            origins: None,
+            related_origins: None,
+            cross_origin: None,
+            top_origins: None,
+            app_id: None,
+            challenge_grace: None,
+            device_bound: None,
+            warn_not_backed_up: None,
+            prf: None,
+        }
+    }
+
+    pub fn build(self) -> Result<RelyingPartyPolicy, Error> {
+        if self.id.is_none() {
+            return Err(Error::EmptyWebauthnPolicy("rp.id".to_string()));
+        }
+        if self.name.is_none() {
+            return Err(Error::EmptyWebauthnPolicy("rp.name".to_string()));
+        }
+        if self.origins.is_none() {
+            return Err(Error::EmptyWebauthnPolicy("rp.origins".to_string()));
+        }
+        if self.cross_origin.is_none() {
+            return Err(Error::EmptyWebauthnPolicy("rp.cross_origin".to_string()));
+        }
+        if self.challenge_grace.is_none() {
+            return Err(Error::EmptyWebauthnPolicy("rp.challenge_grace".to_string()));
+        }
+        if self.device_bound.is_none() {
+            return Err(Error::EmptyWebauthnPolicy("rp.device_bound".to_string()));
+        }
+        if self.warn_not_backed_up.is_none() {
+            return Err(Error::EmptyWebauthnPolicy(
+                "rp.warn_not_backed_up".to_string(),
+            ));
+        }
+        if self.prf.is_none() {
+            return Err(Error::EmptyWebauthnPolicy("rp.prf".to_string()));
+        }
+
+        let policy = RelyingPartyPolicy {
+            id: self.id.unwrap(),
+            name: self.name.unwrap(),
+            origins: self.origins.unwrap(),
+            related_origins: self.related_origins.unwrap_or_default(),
+            cross_origin: self.cross_origin.unwrap(),
+            top_origins: self.top_origins.unwrap_or_default(),
+            app_id: self.app_id.flatten(),
+            challenge_grace: self.challenge_grace.unwrap(),
+            device_bound: self.device_bound.unwrap(),
+            warn_not_backed_up: self.warn_not_backed_up.unwrap(),
+            prf: self.prf.unwrap(),
+        };
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn with_origins(mut self, origins: Vec<AllowedOrigin>) -> Self {
+        self.origins = Some(origins);
+        self
+    }
+
+    /// Optional.  Without related origins only `origins` are accepted.
+    pub fn with_related_origins(mut self, related_origins: Vec<AllowedOrigin>) -> Self {
+        self.related_origins = Some(related_origins);
+        self
+    }
+
+    pub fn with_cross_origin(mut self, cross_origin: bool) -> Self {
+        self.cross_origin = Some(cross_origin);
+        self
+    }
+
+    /// Optional.  Without top origins any embedding site is accepted.
+    pub fn with_top_origins(mut self, top_origins: Vec<AllowedOrigin>) -> Self {
+        self.top_origins = Some(top_origins);
+        self
+    }
+
+    /// Optional.  Without an AppID the legacy extensions are not requested.
+    pub fn with_app_id(mut self, app_id: Option<String>) -> Self {
+        self.app_id = Some(app_id);
+        self
+    }
+
+    pub fn with_challenge_grace(mut self, challenge_grace: usize) -> Self {
+        self.challenge_grace = Some(challenge_grace);
+        self
+    }
+
+    pub fn with_device_bound(mut self, device_bound: DeviceBoundRequirement) -> Self {
+        self.device_bound = Some(device_bound);
+        self
+    }
+
+    pub fn with_warn_not_backed_up(mut self, warn_not_backed_up: bool) -> Self {
+        self.warn_not_backed_up = Some(warn_not_backed_up);
+        self
+    }
+
+    pub fn with_prf(mut self, prf: bool) -> Self {
+        self.prf = Some(prf);
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RegistrationPolicyBuilder {
     pub key_type: Option<PublicKeyCredentialType>,
-    pub alg: Option<COSEAlgorithm>,
+    pub algs: Option<Vec<COSEAlgorithm>>,
     pub authenticator_attachment: Option<AuthenticatorAttachment>,
     pub resident_key: Option<ResidentKeyRequirement>,
     pub user_verification: Option<UserVerificationRequirement>,
-    pub origin: Option<Url>,
     pub attestation: Option<AttestationConveyancePreference>,
     pub timeout: Option<usize>,
-    pub validate_sign_count: Option<bool>,
     pub authenticator_transports: Option<Vec<AuthenticatorTransport>>,
+    pub large_blob: Option<LargeBlobRequirement>,
+    pub cred_protect: Option<CredentialProtectionPolicy>,
+    pub min_pin_length: Option<u32>,
 }
 
-impl Default for WebauthnPolicyBuilder {
+impl Default for RegistrationPolicyBuilder {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl WebauthnPolicyBuilder {
+impl RegistrationPolicyBuilder {
     pub fn new() -> Self {
         Self {
-            rp_id: None,
-            rp_name: None,
             key_type: None,
-            alg: None,
+            algs: None,
             authenticator_attachment: None,
             resident_key: None,
             user_verification: None,
-            origin: None,
             attestation: None,
             timeout: None,
-            validate_sign_count: None,
             authenticator_transports: None,
+            large_blob: None,
+            cred_protect: None,
+            min_pin_length: None,
         }
     }
 
-    pub fn build(self) -> Result<WebauthnPolicy, Error> {
-        if self.rp_id.is_none() {
-            return Err(Error::EmptyWebauthnPolicy("rp_id".to_string()));
-        }
-        if self.rp_name.is_none() {
-            return Err(Error::EmptyWebauthnPolicy("rp_name".to_string()));
-        }
+    pub fn build(self) -> Result<RegistrationPolicy, Error> {
         if self.key_type.is_none() {
-            return Err(Error::EmptyWebauthnPolicy("key_type".to_string()));
+            return Err(Error::EmptyWebauthnPolicy(
+                "registration.key_type".to_string(),
+            ));
         }
-        if self.alg.is_none() {
-            return Err(Error::EmptyWebauthnPolicy("alg".to_string()));
+        if self.algs.as_ref().is_none_or(|algs| algs.is_empty()) {
+            return Err(Error::EmptyWebauthnPolicy("registration.algs".to_string()));
         }
         if self.authenticator_attachment.is_none() {
             return Err(Error::EmptyWebauthnPolicy(
-                "authenticator_attachment".to_string(),
+                "registration.authenticator_attachment".to_string(),
             ));
         }
         if self.resident_key.is_none() {
-            return Err(Error::EmptyWebauthnPolicy("resident_key".to_string()));
+            return Err(Error::EmptyWebauthnPolicy(
+                "registration.resident_key".to_string(),
+            ));
         }
         if self.user_verification.is_none() {
-            return Err(Error::EmptyWebauthnPolicy("user_verification".to_string()));
-        }
-        if self.origin.is_none() {
-            return Err(Error::EmptyWebauthnPolicy("origin".to_string()));
+            return Err(Error::EmptyWebauthnPolicy(
+                "registration.user_verification".to_string(),
+            ));
         }
         if self.attestation.is_none() {
-            return Err(Error::EmptyWebauthnPolicy("attestation".to_string()));
+            return Err(Error::EmptyWebauthnPolicy(
+                "registration.attestation".to_string(),
+            ));
         }
         if self.timeout.is_none() {
-            return Err(Error::EmptyWebauthnPolicy("timeout".to_string()));
-        }
-        if self.validate_sign_count.is_none() {
             return Err(Error::EmptyWebauthnPolicy(
-                "validate_sign_count".to_string(),
+                "registration.timeout".to_string(),
             ));
         }
         if self.authenticator_transports.is_none() {
             return Err(Error::EmptyWebauthnPolicy(
-                "authenticator_transports".to_string(),
+                "registration.authenticator_transports".to_string(),
+            ));
+        }
+        if self.large_blob.is_none() {
+            return Err(Error::EmptyWebauthnPolicy(
+                "registration.large_blob".to_string(),
+            ));
+        }
+        if self.cred_protect.is_none() {
+            return Err(Error::EmptyWebauthnPolicy(
+                "registration.cred_protect".to_string(),
+            ));
+        }
+        if self.min_pin_length.is_none() {
+            return Err(Error::EmptyWebauthnPolicy(
+                "registration.min_pin_length".to_string(),
             ));
         }
 
-        Ok(WebauthnPolicy {
-            rp_id: self.rp_id.unwrap(),
-            rp_name: self.rp_name.unwrap(),
+        let policy = RegistrationPolicy {
             key_type: self.key_type.unwrap(),
-            alg: self.alg.unwrap(),
+            algs: self.algs.unwrap(),
             authenticator_attachment: self.authenticator_attachment.unwrap(),
             resident_key: self.resident_key.unwrap(),
             user_verification: self.user_verification.unwrap(),
-            origin: self.origin.unwrap(),
             attestation: self.attestation.unwrap(),
             timeout: self.timeout.unwrap(),
-            validate_sign_count: self.validate_sign_count.unwrap(),
             authenticator_transports: self.authenticator_transports,
-        })
-    }
-
-    pub fn with_rp_id(mut self, rp_id: String) -> Self {
-        self.rp_id = Some(rp_id);
-        self
-    }
-
-    pub fn with_rp_name(mut self, rp_name: String) -> Self {
-        self.rp_name = Some(rp_name);
-        self
+            large_blob: self.large_blob.unwrap(),
+            cred_protect: self.cred_protect.unwrap(),
+            min_pin_length: self.min_pin_length.unwrap(),
+        };
+        policy.validate()?;
+        Ok(policy)
     }
 
     pub fn with_key_type(mut self, key_type: PublicKeyCredentialType) -> Self {
@@ -183,8 +860,9 @@ impl WebauthnPolicyBuilder {
         self
     }
 
-    pub fn with_alg(mut self, alg: COSEAlgorithm) -> Self {
-        self.alg = Some(alg);
+    /// The algorithms to offer, most preferred first
+    pub fn with_algs(mut self, algs: Vec<COSEAlgorithm>) -> Self {
+        self.algs = Some(algs);
         self
     }
 
@@ -209,11 +887,6 @@ impl WebauthnPolicyBuilder {
         self
     }
 
-    pub fn with_origin(mut self, origin: Url) -> Self {
-        self.origin = Some(origin);
-        self
-    }
-
     pub fn with_attestation(mut self, attestation: AttestationConveyancePreference) -> Self {
         self.attestation = Some(attestation);
         self
@@ -224,10 +897,6 @@ impl WebauthnPolicyBuilder {
         self
     }
 
-    pub fn with_validate_sign_count(mut self, validate_sign_count: bool) -> Self {
-        self.validate_sign_count = Some(validate_sign_count);
-        self
-    }
     pub fn with_authenticator_transports(
         mut self,
         authenticator_transports: Option<Vec<AuthenticatorTransport>>,
@@ -235,4 +904,287 @@ impl WebauthnPolicyBuilder {
         self.authenticator_transports = authenticator_transports;
         self
     }
+
+    pub fn with_large_blob(mut self, large_blob: LargeBlobRequirement) -> Self {
+        self.large_blob = Some(large_blob);
+        self
+    }
+
+    pub fn with_cred_protect(mut self, cred_protect: CredentialProtectionPolicy) -> Self {
+        self.cred_protect = Some(cred_protect);
+        self
+    }
+
+    pub fn with_min_pin_length(mut self, min_pin_length: u32) -> Self {
+        self.min_pin_length = Some(min_pin_length);
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AuthenticationPolicyBuilder {
+    pub user_verification: Option<UserVerificationRequirement>,
+    pub timeout: Option<usize>,
+    pub validate_sign_count: Option<bool>,
+}
+
+impl Default for AuthenticationPolicyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthenticationPolicyBuilder {
+    pub fn new() -> Self {
+        Self {
+            user_verification: None,
+            timeout: None,
+            validate_sign_count: None,
+        }
+    }
+
+    pub fn build(self) -> Result<AuthenticationPolicy, Error> {
+        if self.user_verification.is_none() {
+            return Err(Error::EmptyWebauthnPolicy(
+                "authentication.user_verification".to_string(),
+            ));
+        }
+        if self.timeout.is_none() {
+            return Err(Error::EmptyWebauthnPolicy(
+                "authentication.timeout".to_string(),
+            ));
+        }
+        if self.validate_sign_count.is_none() {
+            return Err(Error::EmptyWebauthnPolicy(
+                "authentication.validate_sign_count".to_string(),
+            ));
+        }
+
+        let policy = AuthenticationPolicy {
+            user_verification: self.user_verification.unwrap(),
+            timeout: self.timeout.unwrap(),
+            validate_sign_count: self.validate_sign_count.unwrap(),
+        };
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn with_user_verification(
+        mut self,
+        user_verification: UserVerificationRequirement,
+    ) -> Self {
+        self.user_verification = Some(user_verification);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: usize) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_validate_sign_count(mut self, validate_sign_count: bool) -> Self {
+        self.validate_sign_count = Some(validate_sign_count);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Algs {
+        #[serde(deserialize_with = "deserialize_algs")]
+        algs: Vec<COSEAlgorithm>,
+    }
+
+    #[test]
+    fn test_deserialize_algs() {
+        let algs: Algs = serde_json::from_str(r#"{"algs": -7}"#).expect("oops");
+        assert_eq!(algs.algs, vec![COSEAlgorithm::ES256]);
+
+        let algs: Algs = serde_json::from_str(r#"{"algs": [-8, -257]}"#).expect("oops");
+        assert_eq!(algs.algs, vec![COSEAlgorithm::EdDSA, COSEAlgorithm::RS256]);
+    }
+
+    #[test]
+    fn test_deserialize_flat_policy() {
+        let json = r#"{
+            "rpId": "localhost",
+            "rpName": "swankymutt",
+            "keyType": "public-key",
+            "alg": -7,
+            "authenticatorAttachment": "platform",
+            "residentKey": "discouraged",
+            "userVerification": "preferred",
+            "origin": "http://localhost:3000",
+            "attestation": "none",
+            "timeout": 60000,
+            "validateSignCount": true,
+            "prf": true
+        }"#;
+        let policy: WebauthnPolicy = serde_json::from_str(json).expect("oops");
+        assert_eq!(policy.rp.id, "localhost");
+        assert_eq!(policy.rp.challenge_grace, DEFAULT_CHALLENGE_GRACE);
+        assert!(policy.rp.prf);
+        assert_eq!(policy.registration.algs, vec![COSEAlgorithm::ES256]);
+        assert_eq!(policy.registration.timeout, 60000);
+        assert_eq!(policy.authentication.timeout, 60000);
+        assert_eq!(
+            policy.authentication.user_verification,
+            UserVerificationRequirement::Preferred
+        );
+        assert!(policy.authentication.validate_sign_count);
+
+        // Once saved again, the policy has sections
+        let json = serde_json::to_string(&policy).expect("oops");
+        let saved: WebauthnPolicy = serde_json::from_str(&json).expect("oops");
+        assert_eq!(saved, policy);
+    }
+
+    #[test]
+    fn test_update_sections() {
+        let mut policy = crate::config::AppConfig::default().webauthn;
+        let patch: WebauthnPolicyBuilder = serde_json::from_str(
+            r#"{"authentication": {"userVerification": "discouraged", "timeout": 30000}}"#,
+        )
+        .expect("oops");
+        let registration = policy.registration.clone();
+        policy.update(patch).expect("oops");
+        assert_eq!(
+            policy.authentication.user_verification,
+            UserVerificationRequirement::Discouraged
+        );
+        assert_eq!(policy.authentication.timeout, 30000);
+        assert_eq!(policy.registration, registration);
+
+        // A flat patch is refused rather than ignored
+        let patch = serde_json::from_str::<WebauthnPolicyBuilder>(r#"{"timeout": 30000}"#);
+        assert!(patch.is_err());
+    }
+
+    #[test]
+    fn test_clear_app_id() {
+        let mut policy = crate::config::AppConfig::default().webauthn;
+        policy.rp.app_id = Some("https://example.com/appid.json".to_string());
+
+        // A patch without appId keeps it
+        let patch: WebauthnPolicyBuilder =
+            serde_json::from_str(r#"{"rp": {"challengeGrace": 30}}"#).expect("oops");
+        policy.update(patch).expect("oops");
+        assert!(policy.rp.app_id.is_some());
+
+        let patch: WebauthnPolicyBuilder =
+            serde_json::from_str(r#"{"rp": {"appId": null}}"#).expect("oops");
+        policy.update(patch).expect("oops");
+        assert_eq!(policy.rp.app_id, None);
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut policy = crate::config::AppConfig::default().webauthn;
+        let patch: WebauthnPolicyBuilder = serde_json::from_str(
+            r#"{
+                "rp": {"id": "example.com"},
+                "registration": {
+                    "timeout": 0,
+                    "authenticatorAttachment": "platform",
+                    "authenticatorTransports": ["internal", "usb"]
+                },
+                "authentication": {"timeout": 0}
+            }"#,
+        )
+        .expect("oops");
+        let violations = match policy.update(patch) {
+            Err(Error::PolicyViolations(violations)) => violations,
+            _ => panic!("expected violations"),
+        };
+        let fields: Vec<&str> = violations.iter().map(|v| v.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "rp.origins",
+                "registration.timeout",
+                "registration.authenticatorTransports",
+                "authentication.timeout"
+            ]
+        );
+
+        let attachment = AuthenticatorAttachment::CrossPlatform;
+        assert!(attachment.allows_transport(&AuthenticatorTransport::Hybrid));
+        assert!(!attachment.allows_transport(&AuthenticatorTransport::Internal));
+    }
+
+    #[test]
+    fn test_origins() {
+        let mut policy = crate::config::AppConfig::default().webauthn.rp;
+        policy.id = "example.com".to_string();
+        policy.origins = serde_json::from_str(
+            r#"["https://example.com", "https://*.example.com", "android:apk-key-hash:AAAA"]"#,
+        )
+        .expect("oops");
+        assert!(policy.validate().is_ok());
+        assert!(policy.allows_origin(&Url::parse("https://login.example.com").expect("oops")));
+        assert!(!policy.allows_origin(&Url::parse("https://example.org").expect("oops")));
+
+        let origin = AllowedOrigin::try_from("https://example.org".to_string()).expect("oops");
+        let patch = RelyingPartyPolicyBuilder::new().with_origins(vec![origin]);
+        assert!(policy.clone().update(patch).is_err());
+
+        let patch = RelyingPartyPolicyBuilder::new().with_id("co.uk".to_string());
+        assert!(policy.update(patch).is_err());
+    }
+
+    #[test]
+    fn test_related_origins() {
+        let mut policy = crate::config::AppConfig::default().webauthn.rp;
+        policy.id = "example.com".to_string();
+        policy.origins = serde_json::from_str(r#"["https://example.com"]"#).expect("oops");
+        policy.related_origins =
+            serde_json::from_str(r#"["https://example.co.uk", "https://www.example.de"]"#)
+                .expect("oops");
+        assert!(policy.validate().is_ok());
+        assert!(policy.allows_origin(&Url::parse("https://example.co.uk").expect("oops")));
+        assert!(!policy.allows_origin(&Url::parse("https://example.fr").expect("oops")));
+
+        // A wildcard could reach any number of origins
+        let json = r#"["https://*.example.co.uk"]"#;
+        policy.related_origins = serde_json::from_str(json).expect("oops");
+        assert!(policy.validate().is_err());
+
+        let json = r#"["https://a.com", "https://b.com", "https://c.com", "https://d.com",
+            "https://e.com", "https://www.e.com", "https://f.com"]"#;
+        policy.related_origins = serde_json::from_str(json).expect("oops");
+        assert!(policy.validate().is_err());
+        policy.related_origins.pop();
+        assert!(policy.validate().is_ok());
+    }
+
+    #[test]
+    fn test_verify_origin() {
+        let mut policy = crate::config::AppConfig::default().webauthn.rp;
+        let json = r#"{
+            "type": "webauthn.get",
+            "challenge": "6P8pAZg6ARXv5SsttAet8XN0IJ1wyF7lZegY-tPq3BgA",
+            "origin": "http://localhost:3000",
+            "crossOrigin": true,
+            "topOrigin": "https://shop.example.org"
+        }"#;
+        let client_data: ClientData = serde_json::from_str(json).expect("oops");
+
+        policy.cross_origin = false;
+        let result = policy.verify_origin(&client_data);
+        assert!(matches!(result, Err(Error::CrossOriginNotAllowed)));
+
+        policy.cross_origin = true;
+        assert!(policy.verify_origin(&client_data).is_ok());
+
+        policy.top_origins = serde_json::from_str(r#"["https://*.example.net"]"#).expect("oops");
+        let result = policy.verify_origin(&client_data);
+        assert!(matches!(result, Err(Error::BadTopOrigin)));
+
+        policy.top_origins = serde_json::from_str(r#"["https://*.example.org"]"#).expect("oops");
+        assert!(policy.verify_origin(&client_data).is_ok());
+    }
 }